@@ -1,4 +1,48 @@
-//! Runner as user...
+//! Runs a build script inside a sandbox (see `alfa::sandbox`)
 
-fn main() {}
+use anyhow::Result;
+use clap::{CommandFactory, FromArgMatches, Parser};
+use std::{env, os::unix::process::CommandExt, process::Command};
 
+use alfa::i18n::{lang_arg, lang_from_args, localize_command, set_lang};
+use alfa::sandbox::Sandbox;
+
+#[derive(Parser)]
+#[command(
+    name = "alfa-runner",
+    version,
+    about = "Run a command in the ALFA build sandbox"
+)]
+struct Cli {
+    /// Build tree which stays writable
+    #[arg(long)]
+    build_dir: String,
+
+    /// Keep access to the host network
+    #[arg(long)]
+    network: bool,
+
+    /// Run the command as this user
+    #[arg(long)]
+    user: Option<String>,
+
+    /// Command to run
+    #[arg(required = true, last = true)]
+    command: Vec<String>,
+}
+
+fn main() -> Result<()> {
+    set_lang(lang_from_args(env::args()));
+    let matches = localize_command(Cli::command().arg(lang_arg())).get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|why| why.exit());
+
+    Sandbox {
+        build_dir: cli.build_dir.into(),
+        network: cli.network,
+        user: cli.user,
+    }
+    .enter()?;
+
+    let err = Command::new(&cli.command[0]).args(&cli.command[1..]).exec();
+    Err(err.into())
+}
@@ -1,31 +1,83 @@
 //! Master file
 
+use std::env;
+use std::fs;
 use std::path::Path;
+use std::process::ExitCode;
 
+use alfa::host::{print_checks, HostRequirements};
+use alfa::i18n::{lang_arg, lang_from_args, localize_command, set_lang};
 use alfa::instruction::Instruction;
-use alfa::tui::{process_msg_result, process_msg_result_err};
+use alfa::tui::{is_interactive, process_msg_result, process_msg_result_err, set_policy, Policy};
 use anyhow::Result;
-use clap::{Parser, Subcommand};
+use clap::{ArgAction, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
 use colored::Colorize;
 
-use alfa::build_meta::{PackageList, PackageOrder};
-use alfa::config::Config;
-use alfa::downloader::{check_md5, download};
-use alfa::prepare::Prepare;
+use alfa::board::Board;
+use alfa::build::Builder;
+use alfa::build_meta::{LastBuild, PackageList, PackageOrder};
+use alfa::config::{Config, EnvDefault};
+use alfa::dashboard::{Dashboard, Item};
+use alfa::downloader::{check_md5, download_any, file_name};
+use alfa::error::{self, AlfaError};
+use alfa::events::{emit, is_human, is_json, set_message_format, Event, MessageFormat};
+use alfa::interrupt;
+use alfa::layers::Layers;
+use alfa::lfs_lists::{self, Import};
+use alfa::logging::{self, console, set_verbosity, Level, Verbosity};
+use alfa::menuconfig;
+use alfa::overrides::Overrides;
+use alfa::pkginfo::{print_info, print_list, PackageEntry};
+use alfa::prepare::{require_root, Prepare};
 use alfa::profile::Profile;
+use alfa::report::{Format, Report};
+use alfa::rootless;
+use alfa::space::Estimate;
+use alfa::spdx;
+use alfa::upstream::{check_all, print_outdated};
 
-use alfa::{msg, process_msg, yesno};
+use alfa::{msg, process_msg, tr, yesno};
 
 #[derive(Debug, Parser)]
 struct Cmd {
     #[command(subcommand)]
     command: Command,
+
+    /// Output format: human-readable messages or newline-delimited JSON
+    /// events
+    #[arg(long, global = true, value_enum, default_value_t)]
+    message_format: MessageFormat,
+
+    /// Answer "yes" to all yes/no questions
+    #[arg(long, global = true, conflicts_with = "no")]
+    yes: bool,
+
+    /// Answer "no" to all yes/no questions
+    #[arg(long, global = true)]
+    no: bool,
+
+    /// Never ask questions: use default answers or fail if a question has
+    /// none (implied when stdin is not a terminal)
+    #[arg(long, global = true)]
+    non_interactive: bool,
+
+    /// Print more details: commands, URLs and paths (`-vv` prints all
+    /// messages)
+    #[arg(short, long, global = true, action = ArgAction::Count, conflicts_with = "quiet")]
+    verbose: u8,
+
+    /// Print only warnings and errors
+    #[arg(short, long, global = true)]
+    quiet: bool,
 }
 
 #[derive(Debug, Subcommand)]
 enum Command {
     /// Generate `.config.toml` file
     Config {
+        #[command(subcommand)]
+        action: Option<ConfigAction>,
+
         /// Specify the `config.toml` file
         #[arg(short, long, default_value_t = String::from("./.config.toml"))]
         config: String,
@@ -33,6 +85,26 @@ enum Command {
         /// Specify the `profile.toml` file
         #[arg(short, long, default_value_t = String::from("./.profile.toml"))]
         profile: String,
+
+        /// Fill target settings from the board profile (e.g. `rock64`)
+        #[arg(short, long)]
+        board: Option<String>,
+
+        /// Directory with board profiles
+        #[arg(long, default_value_t = String::from("./boards"))]
+        boards_dir: String,
+
+        /// Don't ask anything, use the default answers
+        #[arg(long)]
+        defaults: bool,
+
+        /// Override a configuration value (e.g. `system.name=ALFA`)
+        #[arg(short, long, value_name = "KEY=VALUE")]
+        set: Vec<String>,
+
+        /// Read answers from a TOML or JSON file
+        #[arg(short, long)]
+        answers: Option<String>,
     },
 
     /// Prepare for ALFA build (create user, download files, etc.)
@@ -45,15 +117,163 @@ enum Command {
         #[arg(short, long, default_value_t = String::from("./.profile.toml"))]
         profile: String,
 
+        /// Specify the `packages.toml` file
         #[arg(short = 'P', long, default_value_t = String::from("./instructions/packages.toml"))]
         packages: String,
 
+        /// Specify the `pkg_order.toml` file
         #[arg(short, long, default_value_t = String::from("./instructions/pkg_order.toml"))]
         order: String,
+
+        /// Override a configuration value (e.g. `env.MAKEFLAGS=-j8`)
+        #[arg(short, long, value_name = "KEY=VALUE")]
+        set: Vec<String>,
+
+        /// Specify the host requirements file
+        #[arg(short = 'H', long, default_value_t = String::from("./instructions/host.toml"))]
+        host: String,
+
+        /// Continue even if the host system does not meet the requirements
+        /// or there is not enough free space
+        #[arg(long)]
+        force: bool,
+
+        /// Build without root privileges in user and mount namespaces
+        /// (no system account is created)
+        #[arg(long)]
+        rootless: bool,
+
+        /// Show a full-screen dashboard (line-based output is used when the
+        /// terminal is not interactive)
+        #[arg(long)]
+        tui: bool,
+    },
+
+    /// Check that the host system has all required tools
+    CheckHost {
+        /// Specify the host requirements file
+        #[arg(short = 'H', long, default_value_t = String::from("./instructions/host.toml"))]
+        host: String,
+
+        /// Specify the `profile.toml` file (free space is checked in its
+        /// build directory)
+        #[arg(short, long, default_value_t = String::from("./.profile.toml"))]
+        profile: String,
+    },
+
+    /// Generate a report on the last build
+    Report {
+        /// Specify the `config.toml` file
+        #[arg(short, long, default_value_t = String::from("./.config.toml"))]
+        config: String,
+
+        /// Specify the `profile.toml` file
+        #[arg(short, long, default_value_t = String::from("./.profile.toml"))]
+        profile: String,
+
+        /// Specify the host requirements file (tools to report versions of)
+        #[arg(short = 'H', long, default_value_t = String::from("./instructions/host.toml"))]
+        host: String,
+
+        /// Report format
+        #[arg(short, long, value_enum, default_value_t = Format::Markdown)]
+        format: Format,
+
+        /// Write the report to a file instead of stdout
+        #[arg(short, long)]
+        output: Option<String>,
     },
 
     /// Build LFA system from source
-    Build,
+    Build {
+        /// Specify the `config.toml` file
+        #[arg(short, long, default_value_t = String::from("./.config.toml"))]
+        config: String,
+
+        /// Specify the `profile.toml` file
+        #[arg(short, long, default_value_t = String::from("./.profile.toml"))]
+        profile: String,
+
+        /// Specify the `packages.toml` file
+        #[arg(short = 'P', long, default_value_t = String::from("./instructions/packages.toml"))]
+        packages: String,
+
+        /// Specify the `pkg_order.toml` file
+        #[arg(short, long, default_value_t = String::from("./instructions/pkg_order.toml"))]
+        order: String,
+
+        /// Override a configuration value (e.g. `env.MAKEFLAGS=-j8`)
+        #[arg(short, long, value_name = "KEY=VALUE")]
+        set: Vec<String>,
+
+        /// Start the build even if there is not enough free space
+        #[arg(long)]
+        force: bool,
+
+        /// Build without root privileges in user and mount namespaces
+        /// (no system account is created)
+        #[arg(long)]
+        rootless: bool,
+
+        /// Show a full-screen dashboard (line-based output is used when the
+        /// terminal is not interactive)
+        #[arg(long)]
+        tui: bool,
+    },
+
+    /// List packages with their versions, licenses and descriptions
+    List {
+        /// Specify the `packages.toml` file
+        #[arg(short = 'P', long, default_value_t = String::from("./instructions/packages.toml"))]
+        packages: String,
+
+        /// Specify the `pkg_order.toml` file
+        #[arg(short, long, default_value_t = String::from("./instructions/pkg_order.toml"))]
+        order: String,
+    },
+
+    /// Show all information about a package
+    Info {
+        /// Package name (or the name of its build instruction)
+        name: String,
+
+        /// Specify the `packages.toml` file
+        #[arg(short = 'P', long, default_value_t = String::from("./instructions/packages.toml"))]
+        packages: String,
+
+        /// Specify the `pkg_order.toml` file
+        #[arg(short, long, default_value_t = String::from("./instructions/pkg_order.toml"))]
+        order: String,
+    },
+
+    /// Search packages by name, description, home page, license or build
+    /// instruction
+    Search {
+        /// Text to search for (case-insensitive)
+        term: String,
+
+        /// Specify the `packages.toml` file
+        #[arg(short = 'P', long, default_value_t = String::from("./instructions/packages.toml"))]
+        packages: String,
+
+        /// Specify the `pkg_order.toml` file
+        #[arg(short, long, default_value_t = String::from("./instructions/pkg_order.toml"))]
+        order: String,
+    },
+
+    /// Check upstream for newer versions of the packages
+    Outdated {
+        /// Specify the `packages.toml` file
+        #[arg(short = 'P', long, default_value_t = String::from("./instructions/packages.toml"))]
+        packages: String,
+    },
+
+    /// Convert the package list to or from LFS-style `wget-list` and
+    /// `md5sums` files
+    Packages {
+        #[command(subcommand)]
+        action: PackagesAction,
+    },
 
     /// Copy builded files to specified location
     Distcopy {
@@ -68,20 +288,191 @@ enum Command {
     Sysclean,
 }
 
-fn main() -> Result<()> {
-    let cmd = Cmd::parse();
+#[derive(Debug, Subcommand)]
+enum ConfigAction {
+    /// Print the effective configuration (system, user and project files
+    /// merged together)
+    Show {
+        /// Print the file each value came from
+        #[arg(long)]
+        origin: bool,
+
+        /// Override a configuration value (e.g. `system.name=ALFA`)
+        #[arg(short, long, value_name = "KEY=VALUE")]
+        set: Vec<String>,
+    },
 
-    match cmd.command {
-        Command::Config { config, profile } => {
-            let conf = Config::from_stdin()?;
+    /// Edit the configuration in a full-screen menu (creates a new one if
+    /// the file does not exist)
+    Edit,
+}
+
+#[derive(Debug, Subcommand)]
+enum PackagesAction {
+    /// Write `wget-list` and/or `md5sums` files for the packages
+    Export {
+        /// Specify the `packages.toml` file
+        #[arg(short = 'P', long, default_value_t = String::from("./instructions/packages.toml"))]
+        packages: String,
+
+        /// Write download URLs to this file
+        #[arg(long, value_name = "FILE", required_unless_present = "md5sums")]
+        wget_list: Option<String>,
+
+        /// Write MD5 checksums to this file
+        #[arg(long, value_name = "FILE")]
+        md5sums: Option<String>,
+    },
+
+    /// Create a `packages.toml` skeleton from `wget-list` and `md5sums`
+    /// files (names and versions are inferred from archive file names)
+    Import {
+        /// Read download URLs from this file
+        #[arg(long, value_name = "FILE")]
+        wget_list: String,
+
+        /// Read MD5 checksums from this file
+        #[arg(long, value_name = "FILE")]
+        md5sums: Option<String>,
+
+        /// Where to write the `packages.toml` skeleton
+        #[arg(short, long, default_value_t = String::from("./packages.toml"))]
+        output: String,
+
+        /// Overwrite the output file if it exists
+        #[arg(long)]
+        force: bool,
+    },
+}
 
-            process_msg!("\nWrite submited configuration to '{}'", &config.dimmed());
+/// Коды завершения описаны в `alfa::error`
+fn main() -> ExitCode {
+    // язык нужен до разбора аргументов, чтобы перевести справку
+    set_lang(lang_from_args(env::args()));
+    let matches = localize_command(Cmd::command().arg(lang_arg())).get_matches();
+    let cmd = Cmd::from_arg_matches(&matches).unwrap_or_else(|why| why.exit());
+    set_message_format(cmd.message_format);
+    set_verbosity(Verbosity::from_flags(cmd.verbose, cmd.quiet));
+    set_policy(match (cmd.yes, cmd.no, cmd.non_interactive) {
+        (true, _, _) => Policy::Yes,
+        (_, true, _) => Policy::No,
+        (_, _, true) => Policy::NonInteractive,
+        _ => Policy::Ask,
+    });
+
+    match run(cmd) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(why) => {
+            let code = error::exit_code(&why);
+            logging::error(format!("{why:#}"));
+            emit(&Event::Error {
+                message: format!("{why:#}"),
+                kind: error::kind(&why),
+                code,
+            });
+            eprintln!("{}: {why:#}", tr!("tui.error").bold().red());
+            ExitCode::from(code)
+        }
+    }
+}
+
+fn run(cmd: Cmd) -> Result<()> {
+    match cmd.command {
+        Command::Config {
+            action: Some(ConfigAction::Show { origin, set }),
+            config,
+            ..
+        } => {
+            let mut layers = Layers::shared(&Config::defaults())?;
+            layers.push_file(&config, false)?;
+            layers.push_overrides(&Overrides {
+                answers: None,
+                set,
+                use_env: true,
+            })?;
+
+            if is_json() {
+                match origin {
+                    true => {
+                        for (key, value, origin) in layers.origins()? {
+                            emit(&Event::ConfigValue {
+                                key: &key,
+                                value: &value,
+                                origin: Some(&origin),
+                            });
+                        }
+                    }
+                    false => emit(&Event::Config {
+                        value: &layers.value()?,
+                    }),
+                }
+            } else if origin {
+                let values = layers.origins()?;
+                let width = values.iter().map(|(k, _, _)| k.len()).max().unwrap_or(0);
+                for (key, val, origin) in values {
+                    println!("{key:<width$} = {val}  {}", format!("# {origin}").dimmed());
+                }
+            } else {
+                print!("{}", toml::to_string(&layers.value()?)?);
+            }
+        }
+        Command::Config {
+            action: Some(ConfigAction::Edit),
+            config,
+            ..
+        } => {
+            let conf = match Path::new(&config).exists() {
+                true => Config::read(&config)?,
+                false => Layers::shared(&Config::defaults())?.config()?,
+            };
+            menuconfig::edit(conf, &config)?;
+        }
+        Command::Config {
+            action: None,
+            config,
+            profile,
+            board,
+            boards_dir,
+            defaults,
+            set,
+            answers,
+        } => {
+            // мастер настройки запускается только если ни один из источников
+            // неинтерактивной настройки не указан и вопросы задавать можно
+            let interactive = !defaults && set.is_empty() && answers.is_none() && is_interactive();
+
+            // значения существующего `.config.toml` сохраняются и становятся
+            // ответами по умолчанию; устаревший файл обновляется на диске
+            let mut layers = Layers::shared(&Config::defaults())?;
+            layers.push_file(&config, true)?;
+            if let Some(board) = board {
+                let board = Board::find(&boards_dir, &board)?;
+                board.print_summary();
+                EnvDefault::from_board(&board).validate()?;
+                layers.push_board(&board)?;
+            }
+            layers.push_overrides(&Overrides {
+                answers,
+                set,
+                use_env: true,
+            })?;
+
+            let conf = if interactive {
+                Config::from_stdin(layers.config()?)?
+            } else {
+                let conf = layers.config()?;
+                conf.env_default.validate()?;
+                conf
+            };
+
+            msg!();
+            process_msg!(tr!("config.write", path = config.dimmed()));
             match conf.write(&config) {
                 Ok(_) => process_msg_result(true),
                 Err(why) => process_msg_result_err(false, Some(why)),
             }
 
-            process_msg!("Generate build profile to '{}'", &profile.dimmed());
+            process_msg!(tr!("config.write_profile", path = profile.dimmed()));
             let prof = Profile::new(&conf.system);
             match prof.write(&profile) {
                 Ok(_) => process_msg_result(true),
@@ -95,79 +486,444 @@ fn main() -> Result<()> {
             profile,
             packages,
             order,
+            set,
+            host,
+            force,
+            rootless,
+            tui,
         } => {
-            drop(config);
-
+            let config = load_config(&config, set)?;
             let profile = Profile::read(&profile)?;
+            if rootless {
+                enter_rootless(&profile)?;
+            } else {
+                require_root("alfa prepare")?;
+            }
             let packages = PackageList::read(&packages)?;
-            let prepare = Prepare { profile: &profile };
+            let prepare = Prepare {
+                profile: &profile,
+                rootless,
+            };
+
+            msg!(tr!("prepare.check_host"));
+            let host = HostRequirements::read(&host)?;
+            if !print_checks(&host.check(&profile.build_dir)) {
+                if !force {
+                    return Err(anyhow::Error::msg(tr!("prepare.host_failed_force")));
+                }
+                warning(tr!("main.continue_forced"));
+            }
 
-            msg!("Create ALFA dirs...");
-            prepare.create_alfa_dirs()?;
+            msg!(tr!("main.check_space"));
+            let pkg_order = PackageOrder::read(&order)?;
+            let estimate = Estimate::new(&packages, &pkg_order.instructions()?);
+            check_space(&estimate, &config, &profile, force)?;
 
-            /*println!("Create temporary build user...");
-            prepare.create_user()?;*/
+            msg!(tr!("prepare.create_dirs"));
+            prepare.create_alfa_dirs()?;
+            logging::open(&profile.build_dir)?;
 
-            msg!("Download files...");
-            let mut fails = 0;
+            if !rootless {
+                msg!(tr!("prepare.create_build_user"));
+                prepare.create_user()?;
+            }
 
+            msg!(tr!("prepare.download"));
+            interrupt::install();
+            let mut fails = Vec::new();
+            let download_conf = config.download.clone().unwrap_or_default();
+            let src_dir = Path::new(&profile.build_dir).join("src");
+            // при наличии кеша архивы загружаются в него, а затем копируются
+            // в `build_dir/src`
+            let dest_dir = match &download_conf.cache_dir {
+                Some(cache) => {
+                    fs::create_dir_all(cache)?;
+                    Path::new(cache).to_path_buf()
+                }
+                None => src_dir.clone(),
+            };
+
+            let items = packages
+                .package
+                .values()
+                .map(|pkg| {
+                    Item::new(
+                        file_name(&pkg.download).to_string(),
+                        pkg.version.clone(),
+                        None,
+                        None,
+                    )
+                })
+                .collect();
+            let dashboard = match tui {
+                true => Dashboard::start("prepare", items)?,
+                false => None,
+            };
             for pkg in &packages.package {
+                interrupt::check()?;
                 let url = &pkg.1.download;
+                let fname = file_name(url);
                 let client = reqwest::Client::new();
-                download(
-                    &client,
-                    url,
-                    None::<&str>,
-                    &format!("{}/src/", &profile.build_dir),
-                )?;
-
-                process_msg!("Check file");
-                let check = check_md5(
-                    Path::new(&format!("{}/src/", &profile.build_dir))
-                        .join(url.rsplit_once('/').unwrap().1),
-                    &pkg.1.md5,
-                )?;
+                download_any(&client, url, &download_conf.mirrors, &dest_dir)?;
+
+                if dest_dir != src_dir && !src_dir.join(fname).exists() {
+                    fs::copy(dest_dir.join(fname), src_dir.join(fname))?;
+                }
+
+                process_msg!(tr!("prepare.check_file", file = fname.dimmed()));
+                let check = check_md5(src_dir.join(fname), &pkg.1.md5)?;
                 if !check {
-                    fails += 1;
+                    fails.push(fname.to_string());
                 }
                 process_msg_result(check);
+                emit(&Event::Checksum {
+                    file: fname,
+                    expected: &pkg.1.md5,
+                    ok: check,
+                });
             }
 
-            if fails > 0 {
-                if !yesno!("You have a some errors! Continue?") {
-                    panic!();
-                }
+            // вопрос задаётся после закрытия панели
+            drop(dashboard);
+            if !fails.is_empty() && !yesno!(tr!("prepare.checksum_continue", count = fails.len())) {
+                return Err(AlfaError::Checksum { files: fails }.into());
             }
 
-            msg!("Generate build scripts...");
-            let pkg_order = PackageOrder::read(&order)?;
+            msg!(tr!("prepare.gen_scripts"));
+            let env = config.to_env_map();
 
             for pkg in &pkg_order.packages {
-                println!("package {pkg}...");
+                if console(Level::Info) {
+                    println!("{}", tr!("prepare.package", package = pkg));
+                }
 
                 let pkg = format!("{}/{}.toml", &pkg_order.prefix, pkg);
                 let instr = Instruction::read(pkg)?;
 
-                let pkgver = match packages.package.get(&instr.name) {
-                    Some(pkg) => &pkg.version,
-                    None => {
-                        let name = &instr.generic_name;
-                        let name = &name.clone().unwrap_or("".to_string());
-                        match packages.package.get(name) {
-                            Some(pkg) => &pkg.version,
-                            None => "0",
-                        }
-                    }
+                let pkgver = match packages.find(&instr) {
+                    Some((_, pkg)) => &pkg.version,
+                    None => "0",
                 };
 
-                instr.gen_sh(format!("{}/scripts/", &profile.build_dir), &pkgver)?;
+                instr.gen_sh(format!("{}/scripts/", &profile.build_dir), pkgver, &env)?;
+            }
+
+            if !rootless {
+                msg!(tr!("prepare.chown_dirs"));
+                prepare.chown_alfa_dirs()?;
+            }
+
+            msg!(tr!("prepare.shell_env"));
+            prepare.write_shell_env(&config)?;
+
+            msg!(tr!("main.done"));
+            if console(Level::Info) {
+                println!("\n{}", tr!("prepare.next_step"));
             }
+        }
+        Command::CheckHost { host, profile } => {
+            let host = HostRequirements::read(&host)?;
+            let build_dir = match Path::new(&profile).exists() {
+                true => Profile::read(&profile)?.build_dir,
+                false => "/mnt".to_string(),
+            };
+
+            if !print_checks(&host.check(&build_dir)) {
+                return Err(anyhow::Error::msg(tr!("check_host.failed")));
+            }
+        }
+        Command::Build {
+            config,
+            profile,
+            packages,
+            order,
+            set,
+            force,
+            rootless,
+            tui,
+        } => {
+            let config = load_config(&config, set)?;
+            let profile = Profile::read(&profile)?;
+            if rootless {
+                enter_rootless(&profile)?;
+            } else {
+                require_root("alfa build")?;
+            }
+            logging::open(&profile.build_dir)?;
+            let packages = PackageList::read(&packages)?;
+            let pkg_order = PackageOrder::read(&order)?;
+
+            // конфигурация могла измениться после `alfa prepare`
+            msg!(tr!("build.shell_env"));
+            let shell_env = Prepare {
+                profile: &profile,
+                rootless,
+            }
+            .write_shell_env(&config)?;
+            let mut builder = Builder::new(&profile, &packages, &pkg_order, &shell_env)?;
+            builder.rootless = rootless;
+            if let Some(sbu_reference) =
+                config.timing.as_ref().and_then(|t| t.sbu_reference.clone())
+            {
+                builder.sbu_reference = sbu_reference;
+            }
+
+            if builder.has_chroot_steps() {
+                msg!(tr!("build.check_chroot"));
+                builder.detect_qemu(&config.env_default.bits)?;
+                info(match &builder.qemu {
+                    Some(qemu) => tr!(
+                        "build.chroot_qemu",
+                        name = qemu.name,
+                        interpreter = qemu.interpreter.display().to_string().dimmed()
+                    ),
+                    None => tr!("build.chroot_native"),
+                });
+            }
+
+            msg!(tr!("main.check_space"));
+            check_space(&builder.estimate(), &config, &profile, force)?;
+
+            msg!(tr!("build.packages"));
+            let dashboard = match tui {
+                true => Dashboard::start("build", builder.dashboard_items()?)?,
+                false => None,
+            };
+            builder.run()?;
+            drop(dashboard);
+
+            msg!(tr!("main.done"));
+        }
+        Command::Report {
+            config,
+            profile,
+            host,
+            format,
+            output,
+        } => {
+            let config = load_config(&config, Vec::new())?;
+            let profile = Profile::read(&profile)?;
+            let host = HostRequirements::read(&host)?;
+
+            let last_build_path = LastBuild::path(&profile.build_dir);
+            if !last_build_path.exists() {
+                return Err(anyhow::Error::msg(tr!(
+                    "report.no_build",
+                    path = profile.build_dir
+                )));
+            }
+            let last_build = LastBuild::read(&last_build_path)?;
 
-            msg!("Done.");
-            println!("\nPlease execute:\n\tsudo alfa build\nfor build your LFA system.");
+            let report = Report::new(&config, &last_build, &host).render(format)?;
+            match output {
+                Some(pth) => {
+                    fs::write(&pth, report)?;
+                    info(tr!("report.written", path = pth.dimmed()));
+                }
+                None if is_json() => emit(&Event::Report {
+                    format: format.to_possible_value().unwrap().get_name(),
+                    content: report,
+                }),
+                None => print!("{report}"),
+            }
+        }
+        Command::List { packages, order } => {
+            let packages = read_packages(&packages)?;
+            let order = read_order(&order)?;
+            print_list(&PackageEntry::collect(&packages, order.as_ref())?);
+        }
+        Command::Info {
+            name,
+            packages,
+            order,
+        } => {
+            let packages = read_packages(&packages)?;
+            let order = read_order(&order)?;
+            let entries = PackageEntry::collect(&packages, order.as_ref())?;
+            match PackageEntry::find(&entries, &name) {
+                Some(entry) => print_info(entry),
+                None => {
+                    return Err(anyhow::Error::msg(tr!("pkginfo.not_found", name = name)));
+                }
+            }
+        }
+        Command::Search {
+            term,
+            packages,
+            order,
+        } => {
+            let packages = read_packages(&packages)?;
+            let order = read_order(&order)?;
+            let entries = PackageEntry::collect(&packages, order.as_ref())?
+                .into_iter()
+                .filter(|e| e.matches(&term))
+                .collect::<Vec<_>>();
+            match entries.is_empty() {
+                true => info(tr!("pkginfo.no_matches", term = term)),
+                false => print_list(&entries),
+            }
+        }
+        Command::Outdated { packages } => {
+            let packages = PackageList::read(&packages)?;
+            let (results, unchecked) = check_all(&packages, &reqwest::Client::new());
+            print_outdated(&results);
+            if !unchecked.is_empty() {
+                info(tr!("upstream.unchecked", packages = unchecked.join(", ")));
+            }
+        }
+        Command::Packages {
+            action:
+                PackagesAction::Export {
+                    packages,
+                    wget_list,
+                    md5sums,
+                },
+        } => {
+            let packages = PackageList::read(&packages)?;
+            if let Some(pth) = wget_list {
+                fs::write(&pth, lfs_lists::wget_list(&packages))?;
+                info(tr!("lfs_lists.written", path = pth.dimmed()));
+            }
+            if let Some(pth) = md5sums {
+                let (contents, missing) = lfs_lists::md5sums(&packages);
+                fs::write(&pth, contents)?;
+                info(tr!("lfs_lists.written", path = pth.dimmed()));
+                if !missing.is_empty() {
+                    warning(tr!("lfs_lists.no_md5", packages = missing.join(", ")));
+                }
+            }
+        }
+        Command::Packages {
+            action:
+                PackagesAction::Import {
+                    wget_list,
+                    md5sums,
+                    output,
+                    force,
+                },
+        } => {
+            if Path::new(&output).exists() && !force {
+                return Err(anyhow::Error::msg(tr!(
+                    "lfs_lists.output_exists",
+                    path = output
+                )));
+            }
+            let md5sums = md5sums.map(fs::read_to_string).transpose()?;
+            let import = Import::new(&fs::read_to_string(&wget_list)?, md5sums.as_deref());
+            for problem in &import.problems {
+                warning(problem);
+            }
+
+            fs::write(&output, import.to_toml()?)?;
+            info(tr!(
+                "lfs_lists.imported",
+                count = import.packages.package.len(),
+                path = output.dimmed()
+            ));
         }
         _ => todo!(),
     }
 
     Ok(())
 }
+
+fn enter_rootless(profile: &Profile) -> Result<()> {
+    msg!(tr!("main.enter_userns"));
+    let storage = rootless::enter(profile)?;
+    info(tr!(
+        "main.build_tree_mounted",
+        storage = storage.display().to_string().dimmed(),
+        path = profile.build_dir.dimmed()
+    ));
+
+    Ok(())
+}
+
+/// Читает список пакетов и предупреждает о некорректных лицензиях
+fn read_packages(packages: &str) -> Result<PackageList> {
+    let packages = PackageList::read(packages)?;
+    for issue in spdx::check_packages(&packages) {
+        warning(issue);
+    }
+
+    Ok(packages)
+}
+
+/// Читает порядок сборки, если файл существует (без него у пакетов не
+/// будет сборочных инструкций)
+fn read_order(order: &str) -> Result<Option<PackageOrder>> {
+    match Path::new(order).exists() {
+        true => Ok(Some(PackageOrder::read(order)?)),
+        false => Ok(None),
+    }
+}
+
+/// Читает `.config.toml` с учётом общих файлов конфигурации и
+/// переопределений, проверяет параметры целевой системы
+fn load_config(config: &str, set: Vec<String>) -> Result<Config> {
+    let mut layers = Layers::load(config)?;
+    layers.push_overrides(&Overrides {
+        answers: None,
+        set,
+        use_env: true,
+    })?;
+
+    let config = layers.config()?;
+    config.env_default.validate()?;
+
+    Ok(config)
+}
+
+/// Сравнивает оценку необходимого места со свободным местом; при нехватке
+/// места завершает работу с ошибкой, если не указан `--force`
+fn check_space(estimate: &Estimate, config: &Config, profile: &Profile, force: bool) -> Result<()> {
+    let cache_dir = config
+        .download
+        .as_ref()
+        .and_then(|d| d.cache_dir.as_deref());
+    let ok = print_checks(&estimate.check(&profile.build_dir, cache_dir));
+
+    if !estimate.unknown.is_empty() {
+        warning(tr!(
+            "main.no_size_estimates",
+            packages = estimate.unknown.join(", ")
+        ));
+    }
+
+    if !ok {
+        if !force {
+            return Err(anyhow::Error::msg(tr!("main.no_space_force")));
+        }
+        warning(tr!("main.continue_forced"));
+    }
+
+    Ok(())
+}
+
+/// Информационное сообщение (событие `info` в режиме JSON)
+fn info<M: ToString>(message: M) {
+    logging::info(message.to_string());
+    match is_human() {
+        true if console(Level::Info) => println!("{}", message.to_string()),
+        true => {}
+        false => emit(&Event::Info {
+            message: message.to_string(),
+        }),
+    }
+}
+
+/// Предупреждение (событие `warning` в режиме JSON)
+fn warning<M: ToString>(message: M) {
+    logging::warn(message.to_string());
+    match is_human() {
+        true if console(Level::Warn) => println!(
+            "{}: {}",
+            tr!("tui.warning").bold().yellow(),
+            message.to_string()
+        ),
+        true => {}
+        false => emit(&Event::Warning {
+            message: message.to_string(),
+        }),
+    }
+}
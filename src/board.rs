@@ -0,0 +1,182 @@
+//! Board profiles (`boards/*.toml`)
+//!
+//! Профиль платы содержит сведения о SoC, флагах процессора, целевом
+//! триплете, а также параметры сборки загрузчика и img-образа.
+
+use anyhow::Result;
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fs, path::Path};
+use toml;
+
+use crate::config::Bits;
+use crate::error::AlfaError;
+use crate::events::{emit, is_human, Event};
+use crate::tr;
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Board {
+    /// Имя платы (совпадает с именем файла без расширения `*.toml`)
+    pub name: String,
+
+    /// Человекочитаемое название платы
+    pub description: Option<String>,
+    pub soc: String,
+    pub cpu: Cpu,
+
+    /// Целевой триплет, e.g. `aarch64-linux-musl`
+    pub target: String,
+    pub uboot: UBoot,
+    pub firmware: Option<Firmware>,
+
+    /// Путь до DTB относительно `arch/$LFA_ARM_ARCH/boot/dts`
+    pub dtb: String,
+    pub image: ImageLayout,
+}
+
+/// Флаги процессора
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Cpu {
+    pub bits: Bits,
+    pub arch: String,
+    pub float: Option<String>,
+    pub fpu: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct UBoot {
+    /// e.g. `orangepi_pc_defconfig`
+    pub defconfig: String,
+}
+
+/// Прошивки, необходимые для загрузки (BL31 и т.д.)
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Firmware {
+    /// Платформа TF-A (`PLAT=...`)
+    pub tfa_plat: Option<String>,
+
+    /// Платформа rkbin для тех Rockchip SoC, для которых нет BL31 из TF-A
+    pub rkbin_plat: Option<String>,
+}
+
+/// Разметка img-образа
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ImageLayout {
+    /// Тип таблицы разделов: `msdos` или `gpt`
+    pub partition_table: String,
+
+    /// Смещение загрузчика от начала образа (в КиБ)
+    pub uboot_offset: u64,
+
+    /// Размер раздела `/boot` (в МиБ)
+    pub boot_size: u64,
+
+    /// Файловая система корневого раздела
+    pub rootfs_type: String,
+}
+
+impl Board {
+    pub fn read<P: AsRef<Path>>(pth: P) -> Result<Self> {
+        let contents = fs::read_to_string(&pth)?;
+        let data = toml::from_str(&contents)?;
+
+        Ok(data)
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, pth: P) -> Result<()> {
+        let contents = toml::to_string(&self)?;
+        fs::write(&pth, contents)?;
+
+        Ok(())
+    }
+
+    /// Ищет профиль платы с именем `name` в директории `dir`
+    pub fn find<P: AsRef<Path>>(dir: P, name: &str) -> Result<Self> {
+        let pth = dir.as_ref().join(format!("{name}.toml"));
+        if !pth.exists() {
+            return Err(AlfaError::Config(tr!(
+                "board.not_found",
+                name = name,
+                available = Self::list(&dir)?.join(", ")
+            ))
+            .into());
+        }
+
+        let board = Self::read(&pth)?;
+        if board.name != name {
+            return Err(AlfaError::Config(tr!(
+                "board.name_mismatch",
+                path = pth.display(),
+                name = board.name
+            ))
+            .into());
+        }
+
+        Ok(board)
+    }
+
+    /// Возвращает имена всех профилей плат из директории `dir`
+    pub fn list<P: AsRef<Path>>(dir: P) -> Result<Vec<String>> {
+        let mut boards = Vec::new();
+        for entry in fs::read_dir(&dir)? {
+            let pth = entry?.path();
+            if pth.extension().is_some_and(|ext| ext == "toml") {
+                if let Some(stem) = pth.file_stem() {
+                    boards.push(stem.to_string_lossy().to_string());
+                }
+            }
+        }
+        boards.sort();
+
+        Ok(boards)
+    }
+
+    pub fn to_env_map(&self) -> HashMap<&str, String> {
+        let mut map = HashMap::new();
+        map.insert("LFA_BOARD", self.name.clone());
+        map.insert("LFA_SOC", self.soc.clone());
+        map.insert("LFA_UBOOT_DEFCONFIG", self.uboot.defconfig.clone());
+        map.insert("LFA_DTB", self.dtb.clone());
+        map.insert(
+            "LFA_IMG_PARTITION_TABLE",
+            self.image.partition_table.clone(),
+        );
+        map.insert("LFA_IMG_UBOOT_OFFSET", self.image.uboot_offset.to_string());
+        map.insert("LFA_IMG_BOOT_SIZE", self.image.boot_size.to_string());
+        map.insert("LFA_IMG_ROOTFS_TYPE", self.image.rootfs_type.clone());
+
+        if let Some(firmware) = &self.firmware {
+            if let Some(tfa_plat) = firmware.tfa_plat.clone() {
+                map.insert("LFA_TFA_PLAT", tfa_plat);
+            }
+
+            if let Some(rkbin_plat) = firmware.rkbin_plat.clone() {
+                map.insert("LFA_RKBIN_PLAT", rkbin_plat);
+            }
+        }
+
+        map
+    }
+
+    pub fn print_summary(&self) {
+        if !is_human() {
+            emit(&Event::Info {
+                message: format!(
+                    "{} {} ({}, {})",
+                    tr!("board.summary"),
+                    &self.name,
+                    self.description.as_deref().unwrap_or(&self.soc),
+                    &self.target
+                ),
+            });
+            return;
+        }
+        println!(
+            "{} {} ({}, {})",
+            tr!("board.summary").bold(),
+            &self.name,
+            self.description.as_deref().unwrap_or(&self.soc),
+            self.target.dimmed()
+        );
+    }
+}
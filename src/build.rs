@@ -0,0 +1,451 @@
+//! Running build scripts (`alfa build`)
+
+use anyhow::{Error, Result};
+use colored::Colorize;
+use std::{
+    collections::HashMap,
+    env,
+    fs::{self, File},
+    os::unix::process::CommandExt,
+    path::{Path, PathBuf},
+    process::{Child, Command, ExitStatus, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::build_meta::{LastBuild, PackageList, PackageOrder, StepResult, StepStatus};
+use crate::config::Bits;
+use crate::dashboard::Item;
+use crate::downloader::file_name;
+use crate::error::AlfaError;
+use crate::events::{emit, Event};
+use crate::instruction::{Instruction, StageKind};
+use crate::interrupt;
+use crate::logging::{self, console, Level};
+use crate::mounts::Mounts;
+use crate::process_msg;
+use crate::profile::Profile;
+use crate::qemu::Qemu;
+use crate::shell_env::ShellEnv;
+use crate::space::{tree_size, Estimate, PackageUsage, Usage};
+use crate::timing::{
+    children_cpu_time, format_duration, history_path, unix_time, History, Record,
+    DEFAULT_SBU_REFERENCE,
+};
+use crate::tr;
+use crate::tui::process_msg_result;
+
+/// Как часто проверяется, завершился ли сборочный скрипт
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Как часто замеряется место, занятое деревом сборки, во время выполнения
+/// скрипта. Обход дерева крупных пакетов (gcc, glibc) занимает заметное
+/// время, поэтому промежуточные замеры редки; итоговый замер выполняется
+/// после завершения скрипта.
+const MEASURE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Сколько ждать завершения процессов скрипта после `SIGTERM` (и после
+/// `SIGKILL`) при прерывании сборки
+const TERMINATE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Корень дерева сборки внутри chroot
+const CHROOT_ALFA_DIR: &str = "/alfa";
+
+/// Сборка одного пакета
+#[derive(Debug)]
+pub struct Step {
+    pub instr: Instruction,
+
+    /// Ключ пакета в `PackageList`
+    pub package: Option<String>,
+    pub version: String,
+
+    /// Архив с исходным кодом в `build_dir/src`
+    pub archive: Option<PathBuf>,
+    pub script: PathBuf,
+    pub log: PathBuf,
+}
+
+impl Step {
+    pub fn full_name(&self) -> String {
+        format!("{}-{}", &self.instr.name, &self.version)
+    }
+
+    fn result(&self, packages: &PackageList) -> StepResult {
+        let pkg = self
+            .package
+            .as_ref()
+            .and_then(|key| packages.package.get(key));
+
+        StepResult {
+            stage: self.instr.stage.clone(),
+            name: self.instr.name.clone(),
+            package: self.package.clone(),
+            version: self.version.clone(),
+            download: pkg.map(|pkg| pkg.download.clone()),
+            md5: pkg.map(|pkg| pkg.md5.clone()),
+            status: StepStatus::Pending,
+            duration: None,
+            log: self.log.display().to_string(),
+        }
+    }
+}
+
+pub struct Builder<'a> {
+    pub profile: &'a Profile,
+    pub packages: &'a PackageList,
+    pub steps: Vec<Step>,
+
+    /// Окружение сборочных скриптов (см. [`ShellEnv::to_env_map`])
+    pub env: HashMap<String, String>,
+
+    /// Эмулятор для выполнения стадий chroot (см. [`Builder::detect_qemu`])
+    pub qemu: Option<Qemu>,
+
+    /// Пакет, время сборки которого принимается за 1 SBU
+    pub sbu_reference: String,
+
+    /// Режим rootless (см. [`crate::rootless`]). Иначе стадии host
+    /// выполняются от имени сборочного пользователя, а не root.
+    pub rootless: bool,
+}
+
+impl<'a> Builder<'a> {
+    pub fn new(
+        profile: &'a Profile,
+        packages: &'a PackageList,
+        order: &PackageOrder,
+        shell_env: &ShellEnv,
+    ) -> Result<Self> {
+        let build_dir = Path::new(&profile.build_dir);
+        let mut steps = Vec::new();
+
+        for instr in order.instructions()? {
+            let pkg = packages.find(&instr);
+            steps.push(Step {
+                package: pkg.map(|(key, _)| key.clone()),
+                version: pkg.map_or("0".to_string(), |(_, pkg)| pkg.version.clone()),
+                archive: pkg.map(|(_, pkg)| build_dir.join("src").join(file_name(&pkg.download))),
+                script: instr.script_path(build_dir.join("scripts")),
+                log: build_dir
+                    .join("logs")
+                    .join(&instr.stage)
+                    .join(format!("{}.log", &instr.name)),
+                instr,
+            });
+        }
+
+        Ok(Self {
+            profile,
+            packages,
+            steps,
+            env: shell_env.to_env_map(),
+            qemu: None,
+            sbu_reference: DEFAULT_SBU_REFERENCE.to_string(),
+            rootless: false,
+        })
+    }
+
+    pub fn has_chroot_steps(&self) -> bool {
+        self.steps
+            .iter()
+            .any(|step| step.instr.kind == StageKind::Chroot)
+    }
+
+    /// Находит эмулятор qemu-user, если стадии chroot не могут быть
+    /// выполнены на хост-системе без эмуляции
+    pub fn detect_qemu(&mut self, bits: &Bits) -> Result<()> {
+        if self.has_chroot_steps() {
+            self.qemu = Qemu::detect(bits)?;
+        }
+
+        Ok(())
+    }
+
+    /// Очередь панели сборки с ожидаемой длительностью сборки пакетов
+    pub fn dashboard_items(&self) -> Result<Vec<Item>> {
+        let history = match history_path() {
+            Some(pth) => History::read(pth)?,
+            None => History::default(),
+        };
+
+        Ok(self
+            .steps
+            .iter()
+            .map(|step| {
+                Item::new(
+                    format!("{}/{}", &step.instr.stage, &step.instr.name),
+                    step.version.clone(),
+                    Some(step.log.clone()),
+                    history.last(&step.instr.name).map(|rec| rec.wall),
+                )
+            })
+            .collect())
+    }
+
+    pub fn estimate(&self) -> Estimate {
+        let instrs = self.steps.iter().map(|s| &s.instr);
+        Estimate::new(self.packages, instrs)
+    }
+
+    /// Последовательно собирает все пакеты; останавливается на первой ошибке.
+    /// При прерывании (Ctrl-C) текущий скрипт завершается, а точки
+    /// монтирования chroot размонтируются.
+    pub fn run(&self) -> Result<()> {
+        interrupt::install();
+
+        let history_path = history_path();
+        let mut history = match &history_path {
+            Some(pth) => History::read(pth)?,
+            None => History::default(),
+        };
+
+        let build_dir = Path::new(&self.profile.build_dir);
+        let last_build_path = LastBuild::path(build_dir);
+        let mut last_build = LastBuild {
+            started: unix_time(),
+            finished: None,
+            step: self.steps.iter().map(|s| s.result(self.packages)).collect(),
+        };
+        last_build.write(&last_build_path)?;
+
+        for (i, step) in self.steps.iter().enumerate() {
+            interrupt::check()?;
+
+            let (eta, unknown) = history.eta(self.steps[i..].iter().map(|s| s.instr.name.as_str()));
+            let mut progress = format!("{}/{}", i + 1, self.steps.len());
+            if eta > 0.0 {
+                let approx = if unknown.is_empty() { "" } else { ">" };
+                progress = format!("{progress}, ETA {approx}{}", format_duration(eta));
+            }
+
+            let started = Instant::now();
+            let rslt = self.run_step(step, &progress);
+            let result = &mut last_build.step[i];
+            result.duration = Some(started.elapsed().as_secs_f64());
+            result.status = match &rslt {
+                Ok(_) => StepStatus::Ok,
+                Err(_) if interrupt::interrupted() => StepStatus::Interrupted,
+                Err(_) => StepStatus::Failed,
+            };
+            let (status, duration) = (result.status, result.duration.unwrap_or(0.0));
+            last_build.write(&last_build_path)?;
+
+            let rec = rslt.map(|rec| history.push(&step.instr.name, rec, &self.sbu_reference));
+            emit(&Event::PackageFinished {
+                stage: &step.instr.stage,
+                package: &step.instr.name,
+                version: &step.version,
+                status,
+                duration,
+                sbu: rec.as_ref().ok().and_then(|rec| rec.sbu),
+                log: step.log.display().to_string(),
+            });
+            let rec = rec?;
+            logging::info(format!("{}: {}", step.full_name(), rec.summary()));
+            if console(Level::Info) {
+                println!("  {}", rec.summary().dimmed());
+            }
+
+            if let Some(pth) = &history_path {
+                history.write(pth)?;
+            }
+        }
+
+        last_build.finished = Some(unix_time());
+        last_build.write(&last_build_path)?;
+
+        Ok(())
+    }
+
+    fn run_step(&self, step: &Step, progress: &str) -> Result<Record> {
+        let build_dir = Path::new(&self.profile.build_dir);
+
+        if let Some(dir) = step.log.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let log = File::create(&step.log)?;
+
+        // размонтируются при выходе из функции, в т.ч. при ошибке
+        let _mounts;
+        let mut cmd = Command::new(runner_path()?);
+        cmd.arg("--build-dir").arg(build_dir);
+        if step.instr.network {
+            cmd.arg("--network");
+        }
+        if step.instr.kind == StageKind::Host && !self.rootless {
+            cmd.arg("--user").arg(&self.profile.user_name);
+        }
+        cmd.arg("--");
+
+        match step.instr.kind {
+            StageKind::Host => {
+                cmd.args(["bash", "-e"])
+                    .arg(&step.script)
+                    .env_clear()
+                    .envs(&self.env)
+                    .current_dir(build_dir.join("src"));
+            }
+            StageKind::Chroot => {
+                let root = build_dir.join("lfa");
+                _mounts = Mounts::chroot(&root, build_dir, self.qemu.as_ref())?;
+
+                cmd.arg("chroot")
+                    .arg(&root)
+                    .args(["/bin/bash", "-e"])
+                    .arg(step.instr.script_path(format!("{CHROOT_ALFA_DIR}/scripts")))
+                    .env_clear()
+                    .envs(chroot_env())
+                    .current_dir(&root);
+            }
+        }
+
+        process_msg!(tr!(
+            "build.package",
+            package = step.full_name().dimmed(),
+            progress = progress
+        ));
+        emit(&Event::PackageStarted {
+            stage: &step.instr.stage,
+            package: &step.instr.name,
+            version: &step.version,
+            log: step.log.display().to_string(),
+        });
+        let args = cmd
+            .get_args()
+            .map(|a| a.to_string_lossy())
+            .collect::<Vec<_>>();
+        logging::debug(tr!(
+            "build.run",
+            command = format!("{} {}", cmd.get_program().to_string_lossy(), args.join(" ")),
+            log = step.log.display()
+        ));
+
+        let used_before = tree_size(build_dir);
+        let (started, cpu_before) = (Instant::now(), children_cpu_time());
+        // скрипт и все запущенные им процессы (`make`, `gcc` и т.д.)
+        // образуют отдельную группу, которая завершается целиком
+        let mut child = cmd
+            .process_group(0)
+            .stdin(Stdio::null())
+            .stdout(log.try_clone()?)
+            .stderr(log)
+            .spawn()?;
+
+        // пиковое место, занятое деревом сборки
+        let mut peak = used_before;
+        let mut measured = Instant::now();
+        let status: ExitStatus = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if interrupt::interrupted() {
+                // точки монтирования `_mounts` размонтируются только после
+                // завершения всей группы
+                terminate(&mut child)?;
+                process_msg_result(false);
+                return Err(AlfaError::Interrupted.into());
+            }
+            if measured.elapsed() >= MEASURE_INTERVAL {
+                peak = peak.max(tree_size(build_dir));
+                measured = Instant::now();
+            }
+            thread::sleep(POLL_INTERVAL);
+        };
+        peak = peak.max(tree_size(build_dir));
+        let rec = Record::new(
+            &step.version,
+            started.elapsed().as_secs_f64(),
+            children_cpu_time() - cpu_before,
+        );
+
+        process_msg_result(status.success());
+        if !status.success() {
+            return Err(AlfaError::Script {
+                name: step.full_name(),
+                status: status.to_string(),
+                log: step.log.clone(),
+            }
+            .into());
+        }
+
+        if let Some(pkg) = &step.package {
+            let size_source = step
+                .archive
+                .as_ref()
+                .and_then(|a| fs::metadata(a).ok())
+                .map_or(0, |meta| meta.len().div_ceil(1024 * 1024));
+            Usage::record(
+                build_dir.join("usage.toml"),
+                pkg,
+                PackageUsage {
+                    size_source,
+                    size_build: peak.saturating_sub(used_before).div_ceil(1024 * 1024),
+                },
+            )?;
+        }
+
+        Ok(rec)
+    }
+}
+
+/// Завершает группу процессов сборочного скрипта: `SIGTERM`, затем (если
+/// процессы не завершились за [`TERMINATE_TIMEOUT`]) `SIGKILL`. Возвращает
+/// управление после того, как `alfa-runner` и все процессы группы
+/// завершились.
+fn terminate(child: &mut Child) -> Result<()> {
+    let pgid = child.id() as libc::pid_t;
+    let group_alive = || {
+        // SAFETY: сигнал 0 только проверяет существование процессов
+        (unsafe { libc::killpg(pgid, 0) }) == 0
+            || std::io::Error::last_os_error().raw_os_error() != Some(libc::ESRCH)
+    };
+    let wait_group = |child: &mut Child| -> Result<bool> {
+        let deadline = Instant::now() + TERMINATE_TIMEOUT;
+        while Instant::now() < deadline {
+            if child.try_wait()?.is_some() && !group_alive() {
+                return Ok(true);
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+        Ok(false)
+    };
+
+    for signal in [libc::SIGTERM, libc::SIGKILL] {
+        // SAFETY: вызов не затрагивает память процесса
+        unsafe { libc::killpg(pgid, signal) };
+        if wait_group(child)? {
+            return Ok(());
+        }
+    }
+    child.wait()?;
+
+    Ok(())
+}
+
+/// `alfa-runner`, выполняющий скрипты в песочнице, устанавливается рядом с
+/// `alfa`
+fn runner_path() -> Result<PathBuf> {
+    let pth = env::current_exe()?.with_file_name("alfa-runner");
+    if !pth.exists() {
+        return Err(Error::msg(tr!("build.no_runner", path = pth.display())));
+    }
+
+    Ok(pth)
+}
+
+/// Чистое окружение скриптов, выполняемых в chroot (аналогично главе 7.4
+/// руководства LFS)
+fn chroot_env() -> HashMap<String, String> {
+    let mut map = HashMap::new();
+    map.insert("HOME".to_string(), "/root".to_string());
+    map.insert(
+        "TERM".to_string(),
+        env::var("TERM").unwrap_or("dumb".to_string()),
+    );
+    map.insert("PATH".to_string(), "/usr/bin:/usr/sbin".to_string());
+    map.insert("ALFA_BUILD_DIR".to_string(), CHROOT_ALFA_DIR.to_string());
+    map.insert("ALFA_SRC_DIR".to_string(), format!("{CHROOT_ALFA_DIR}/src"));
+
+    map
+}
@@ -1,14 +1,23 @@
 //! Information about build process
 
 use anyhow::Result;
+use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, fs, path::Path};
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+};
 use toml;
 
+use crate::i18n::Localized;
+use crate::instruction::Instruction;
+use crate::upstream::UpstreamCheck;
+
 // NOTE: можно использовать файл `packages.toml` из руководства LFA
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct PackageList {
-    pub package: HashMap<String, Package>,
+    /// Пакеты в порядке их следования в файле (`write` его сохраняет)
+    pub package: IndexMap<String, Package>,
 }
 
 impl PackageList {
@@ -25,14 +34,57 @@ impl PackageList {
 
         Ok(())
     }
+
+    /// Пакет, соответствующий инструкции: ищется по `name`, затем по
+    /// `generic_name`. Возвращает ключ пакета и сам пакет.
+    pub fn find(&self, instr: &Instruction) -> Option<(&String, &Package)> {
+        self.package.get_key_value(&instr.name).or_else(|| {
+            instr
+                .generic_name
+                .as_ref()
+                .and_then(|name| self.package.get_key_value(name))
+        })
+    }
 }
 
 // Информация о пакете
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Package {
     pub version: String,
+
+    /// Описание пакета: одна строка или варианты на разных языках
+    /// (`description = { ru = "...", en = "..." }`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<Localized>,
+
+    /// Домашняя страница проекта
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub home_page: Option<String>,
+
+    /// Лицензия в виде выражения SPDX (e.g. `GPL-2.0-only`,
+    /// `LGPL-3.0-or-later OR GPL-2.0-or-later`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
+
     pub download: String,
     pub md5: String,
+
+    /// Примерный размер архива с исходным кодом (в МиБ)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size_source: Option<u64>,
+
+    /// Примерный объём места, занимаемого при сборке и установке пакета (в МиБ)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size_build: Option<u64>,
+
+    /// Примечания (e.g. особенности сборки); как и описание, могут быть
+    /// заданы на разных языках
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<Localized>,
+
+    /// Правило проверки новых версий (`alfa outdated`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub check: Option<UpstreamCheck>,
 }
 
 /// Порядок сборки пакетов
@@ -47,6 +99,51 @@ pub struct PackageOrder {
 }
 
 impl PackageOrder {
+    /// Читает сборочные инструкции всех пакетов в порядке сборки
+    pub fn instructions(&self) -> Result<Vec<Instruction>> {
+        let mut instrs = Vec::with_capacity(self.packages.len());
+        for pkg in &self.packages {
+            instrs.push(Instruction::read(format!("{}/{}.toml", &self.prefix, pkg))?);
+        }
+
+        Ok(instrs)
+    }
+
+    pub fn read<P: AsRef<Path>>(pth: P) -> Result<Self> {
+        let contents = fs::read_to_string(&pth)?;
+        let data = toml::from_str(&contents)?;
+
+        Ok(data)
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, pth: P) -> Result<()> {
+        let contents = toml::to_string(&self)?;
+        fs::write(&pth, contents)?;
+
+        Ok(())
+    }
+}
+
+/// Результаты последнего запуска `alfa build` (`build_dir/last_build.toml`)
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct LastBuild {
+    /// Время начала сборки (UNIX time)
+    pub started: u64,
+
+    /// Время окончания сборки (UNIX time); отсутствует, если сборка ещё
+    /// выполняется или была аварийно завершена
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finished: Option<u64>,
+
+    #[serde(default)]
+    pub step: Vec<StepResult>,
+}
+
+impl LastBuild {
+    pub fn path<P: AsRef<Path>>(build_dir: P) -> PathBuf {
+        build_dir.as_ref().join("last_build.toml")
+    }
+
     pub fn read<P: AsRef<Path>>(pth: P) -> Result<Self> {
         let contents = fs::read_to_string(&pth)?;
         let data = toml::from_str(&contents)?;
@@ -61,3 +158,50 @@ impl PackageOrder {
         Ok(())
     }
 }
+
+/// Результат сборки одного пакета
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct StepResult {
+    pub stage: String,
+    pub name: String,
+
+    /// Ключ пакета в `PackageList`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub package: Option<String>,
+
+    /// Версия, адрес архива и контрольная сумма пакета на момент сборки
+    /// (`packages.toml` мог измениться после неё)
+    pub version: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub download: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub md5: Option<String>,
+    pub status: StepStatus,
+
+    /// Длительность сборки (в секундах)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<f64>,
+    pub log: String,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum StepStatus {
+    /// Сборка пакета не начиналась
+    Pending,
+    Ok,
+    Failed,
+    Interrupted,
+}
+
+impl fmt::Display for StepStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::Pending => "pending",
+            Self::Ok => "ok",
+            Self::Failed => "failed",
+            Self::Interrupted => "interrupted",
+        };
+        write!(f, "{s}")
+    }
+}
@@ -3,24 +3,35 @@
 use anyhow::Result;
 use colored::Colorize;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, fs, path::Path, str::FromStr};
+use std::{collections::HashMap, env, fmt, fs, path::Path, str::FromStr};
 use toml;
 
+use crate::board::Board;
+use crate::schema::{self, Schema};
+use crate::tr;
 use crate::tui::answer;
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Config {
+    pub schema_version: i64,
     pub system: System,
     pub env_default: EnvDefault,
     pub env: HashMap<String, String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub download: Option<Download>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timing: Option<Timing>,
+
+    /// Профиль платы, из которого были взяты параметры `env_default`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub board: Option<Board>,
 }
 
 impl Config {
     pub fn read<P: AsRef<Path>>(pth: P) -> Result<Self> {
-        let contents = fs::read_to_string(&pth)?;
-        let data = toml::from_str(&contents)?;
-
-        Ok(data)
+        schema::read(Schema::Config, pth)
     }
 
     pub fn write<P: AsRef<Path>>(&self, pth: P) -> Result<()> {
@@ -30,36 +41,108 @@ impl Config {
         Ok(())
     }
 
-    pub fn from_stdin() -> Result<Self> {
+    /// Значения по умолчанию мастера настройки
+    pub fn defaults() -> Self {
+        Self {
+            schema_version: schema::CONFIG_VERSION,
+            system: System {
+                name: "ALFA".to_string(),
+                version: env!("CARGO_PKG_VERSION").to_string(),
+                author: env::var("USER").unwrap_or_default(),
+            },
+            env_default: EnvDefault {
+                bits: Bits::Arm64,
+                lfa_host: default_host(),
+                lfa_tgt: "aarch64-linux-musl".to_string(),
+                lfa_arch: "armv8.1-a".to_string(),
+                lfa_float: None,
+                lfa_fpu: None,
+            },
+            env: HashMap::new(),
+            download: None,
+            timing: None,
+            board: None,
+        }
+    }
+
+    /// Запрашивает параметры у пользователя, предлагая значения из `base` в
+    /// качестве значений по умолчанию. Если в `base` указан профиль платы, то
+    /// `env_default` берётся из него без вопросов.
+    pub fn from_stdin(base: Self) -> Result<Self> {
         Ok(Self {
-            system: System::from_stdin()?,
-            env_default: EnvDefault::from_stdin()?,
-            env: {
-                let mut envs = HashMap::new();
-                let is_set_str = answer(
-                    "Set additional environment variables (y/n)?",
-                    Some("n".to_string()),
-                )?;
-                let is_set = &is_set_str == "y" || &is_set_str == "Y";
-
-                if !is_set {
-                    envs
-                } else {
-                    loop {
-                        let k = answer("\tname ('end' for exit)", None)?;
-                        if &k == "end" {
-                            break;
-                        }
-                        let v = answer(format!("\tvalue of '{}'", &k.dimmed()), None)?;
-                        println!(); // отступ в 1 строку чтобы визуально разделить ввод значений переменных
-
-                        envs.insert(k, v);
-                    }
-                    envs
-                }
+            schema_version: schema::CONFIG_VERSION,
+            system: System::from_stdin(&base.system)?,
+            env_default: match &base.board {
+                Some(_) => base.env_default,
+                None => EnvDefault::from_stdin(&base.env_default)?,
             },
+            env: env_from_stdin(base.env)?,
+            download: base.download,
+            timing: base.timing,
+            board: base.board,
         })
     }
+
+    /// Все переменные окружения, которые передаются сборочным инструкциям
+    pub fn to_env_map(&self) -> HashMap<String, String> {
+        let mut map = HashMap::new();
+        for (k, v) in self.env_default.to_env_map() {
+            map.insert(k.to_string(), v);
+        }
+
+        if let Some(board) = &self.board {
+            for (k, v) in board.to_env_map() {
+                map.insert(k.to_string(), v);
+            }
+        }
+
+        for (k, v) in &self.env {
+            map.insert(k.clone(), v.clone());
+        }
+
+        map
+    }
+}
+
+fn env_from_stdin(mut envs: HashMap<String, String>) -> Result<HashMap<String, String>> {
+    let is_set_str = answer(tr!("config.set_env"), Some("n".to_string()))?;
+    let is_set = &is_set_str == "y" || &is_set_str == "Y";
+
+    if is_set {
+        loop {
+            let k = answer(tr!("config.env_name"), None)?;
+            if &k == "end" {
+                break;
+            }
+            let v = answer(tr!("config.env_value", name = k.dimmed()), None)?;
+            println!(); // отступ в 1 строку чтобы визуально разделить ввод значений переменных
+
+            envs.insert(k, v);
+        }
+    }
+
+    Ok(envs)
+}
+
+/// Параметры загрузки архивов с исходным кодом
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct Download {
+    /// Зеркала, с которых архивы загружаются в первую очередь (к адресу
+    /// зеркала добавляется имя файла)
+    #[serde(default)]
+    pub mirrors: Vec<String>,
+
+    /// Общий кеш архивов; уже загруженные в него архивы повторно не
+    /// загружаются
+    pub cache_dir: Option<String>,
+}
+
+/// Параметры измерения времени сборки
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct Timing {
+    /// Пакет, время сборки которого принимается за 1 SBU (по умолчанию
+    /// `binutils-pass1`)
+    pub sbu_reference: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -70,20 +153,26 @@ pub struct System {
 }
 
 impl System {
-    pub fn from_stdin() -> Result<Self> {
-        println!("{}", "Specify information about ALFA System:".bold());
+    pub fn from_stdin(def: &Self) -> Result<Self> {
+        println!("{}", tr!("config.wizard_system").bold());
 
         Ok(Self {
-            name: answer("System name", Some("ALFA".to_string()))?,
-            version: answer(
-                "System version",
-                Some(env!("CARGO_PKG_VERSION").to_string()),
+            name: answer(tr!("config.system_name"), Some(def.name.clone()))?,
+            version: answer(tr!("config.system_version"), Some(def.version.clone()))?,
+            author: answer(
+                tr!("config.system_author"),
+                Some(def.author.clone()).filter(|a| !a.is_empty()),
             )?,
-            author: answer("System author (builder)", None)?,
         })
     }
 }
 
+/// Триплет кросс-компилятора хост-системы (`<arch>-cross-linux-gnu`, как в
+/// руководстве LFA)
+pub fn default_host() -> String {
+    format!("{}-cross-linux-gnu", env::consts::ARCH)
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct EnvDefault {
     pub bits: Bits,
@@ -95,52 +184,88 @@ pub struct EnvDefault {
 }
 
 impl EnvDefault {
-    pub fn from_stdin() -> Result<Self> {
-        println!("\n{}", "Set the default environment variables".bold());
+    pub fn from_stdin(def: &Self) -> Result<Self> {
+        println!("\n{}", tr!("config.wizard_env").bold());
+
+        let mut env = Self::ask(Some(def))?;
+        // повторяем ввод до тех пор, пока параметры не будут согласованы
+        // друг с другом; ранее введённые значения становятся значениями
+        // по умолчанию
+        while let Err(why) = env.validate() {
+            println!("\n{}\n", why.to_string().red());
+            env = Self::ask(Some(&env))?;
+        }
+
+        Ok(env)
+    }
+
+    fn ask(prev: Option<&Self>) -> Result<Self> {
+        let bits = loop {
+            let def = prev.map_or("64".to_string(), |p| p.bits.to_string());
+            let b = answer(tr!("config.bits"), Some(def))?;
+            if let Ok(bits) = Bits::from_str(&b) {
+                break bits;
+            }
+        };
+        // при смене разрядности прежние значения по умолчанию не подходят
+        let prev = prev.filter(|p| p.bits == bits);
+        let (def_tgt, def_arch) = match bits {
+            Bits::Arm32 => ("arm-linux-musleabihf", "armv7-a"),
+            Bits::Arm64 => ("aarch64-linux-musl", "armv8.1-a"),
+        };
+        let def =
+            |val: Option<&String>, def: &str| Some(val.map_or(def.to_string(), String::clone));
 
         Ok(Self {
-            bits: {
-                #[allow(unused_assignments)]
-                let mut b = String::new();
-                let bits: Bits;
-
-                loop {
-                    b = answer("Bits (32/64)", Some("64".to_string()))?;
-                    if let Ok(_bits) = Bits::from_str(&b) {
-                        bits = _bits;
-                        break;
+            lfa_host: answer(
+                tr!("config.host"),
+                def(prev.map(|p| &p.lfa_host), &default_host()),
+            )?,
+            lfa_tgt: answer(tr!("config.target"), def(prev.map(|p| &p.lfa_tgt), def_tgt))?,
+            lfa_arch: answer(tr!("config.arch"), def(prev.map(|p| &p.lfa_arch), def_arch))?,
+            lfa_float: match bits {
+                Bits::Arm64 => None,
+                Bits::Arm32 => {
+                    let float = answer(
+                        tr!("config.float"),
+                        def(prev.and_then(|p| p.lfa_float.as_ref()), "hard"),
+                    )?;
+                    if float.is_empty() {
+                        None
+                    } else {
+                        Some(float)
                     }
                 }
-                bits
-            },
-            lfa_host: answer("Your host", Some("x86_64-cross-linux-gnu".to_string()))?,
-            lfa_tgt: answer("Target", Some("aarch64-linux-musleabihf".to_string()))?,
-            lfa_arch: answer("CPU Architecture", Some("armv8.1-a".to_string()))?,
-            lfa_float: {
-                let float = answer(
-                    format!("Float type ({})", "FOR ALL BUT ARM-V8 ARCHITECTURES".bold()),
-                    Some("".to_string()),
-                )?;
-                if float.is_empty() {
-                    None
-                } else {
-                    Some(float)
-                }
             },
-            lfa_fpu: {
-                let fpu = answer(
-                    format!("FPU type ({})", "FOR ALL BUT ARM-V8 ARCHITECTURES".bold()),
-                    Some("".to_string()),
-                )?;
-                if fpu.is_empty() {
-                    None
-                } else {
-                    Some(fpu)
+            lfa_fpu: match bits {
+                Bits::Arm64 => None,
+                Bits::Arm32 => {
+                    let fpu = answer(
+                        tr!("config.fpu"),
+                        def(prev.and_then(|p| p.lfa_fpu.as_ref()), "neon-vfpv4"),
+                    )?;
+                    if fpu.is_empty() {
+                        None
+                    } else {
+                        Some(fpu)
+                    }
                 }
             },
+            bits,
         })
     }
 
+    pub fn from_board(board: &Board) -> Self {
+        Self {
+            bits: board.cpu.bits.clone(),
+            lfa_host: default_host(),
+            lfa_tgt: board.target.clone(),
+            lfa_arch: board.cpu.arch.clone(),
+            lfa_float: board.cpu.float.clone(),
+            lfa_fpu: board.cpu.fpu.clone(),
+        }
+    }
+
     pub fn to_env_map(&self) -> HashMap<&str, String> {
         let mut map = HashMap::new();
         map.insert("LFA_ARM_ARCH", self.bits.to_string());
@@ -160,22 +285,22 @@ impl EnvDefault {
     }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 pub enum Bits {
-    #[serde(rename = "arm")]
+    #[serde(rename = "arm", alias = "arm32", alias = "32")]
     Arm32,
 
-    #[serde(rename = "arm64")]
+    #[serde(rename = "arm64", alias = "64")]
     Arm64,
 }
 
-impl ToString for Bits {
-    fn to_string(&self) -> String {
-        match self {
+impl fmt::Display for Bits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
             Self::Arm32 => "arm",
             Self::Arm64 => "arm64",
-        }
-        .to_string()
+        };
+        write!(f, "{s}")
     }
 }
 
@@ -186,7 +311,7 @@ impl FromStr for Bits {
         match s {
             "arm" | "arm32" | "32" => Ok(Self::Arm32),
             "arm64" | "64" => Ok(Self::Arm64),
-            _ => Err(format!("arch type \"{}\" is incorrect!", s.bold().red())),
+            _ => Err(tr!("config.bad_bits", bits = s)),
         }
     }
 }
@@ -0,0 +1,672 @@
+//! Full-screen build dashboard (`--tui`)
+//!
+//! Панель показывает очередь пакетов и их состояние, собираемый пакет и
+//! текущий этап, последние строки журнала сборки, ход загрузки архивов,
+//! прошедшее и оставшееся время. Панель получает события `alfa::events` и
+//! отрисовывается в отдельном потоке. Если stdin или stdout не является
+//! терминалом, а также в режиме JSON, панель не открывается и используется
+//! построчный вывод.
+//!
+//! Клавиши: `↑`/`↓` - выбор пакета, `Enter` - журнал выбранного пакета
+//! (`↑`/`↓`/`PgUp`/`PgDn`/`Home`/`End` - прокрутка, `Esc` - назад),
+//! `Ctrl-C` - прервать работу.
+
+use anyhow::Result;
+use colored::Colorize;
+use ratatui::{
+    crossterm::event::{self, Event as Input, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, Gauge, List, ListItem, ListState, Paragraph},
+    DefaultTerminal, Frame,
+};
+use std::{
+    fs::{self, File},
+    io::{stdin, stdout, IsTerminal, Read, Seek, SeekFrom},
+    panic,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, MutexGuard, Once,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::build_meta::StepStatus;
+use crate::events::{self, is_json, Event};
+use crate::timing::format_duration;
+use crate::tr;
+
+/// Интервал перерисовки панели
+const FRAME_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Сколько байт с конца журнала читается для вывода его последних строк
+const TAIL_BYTES: u64 = 16 * 1024;
+
+/// Сколько последних сообщений (предупреждений) выводится под очередью
+const MESSAGES_SHOWN: usize = 3;
+
+/// Сколько загрузок выводится одновременно
+const DOWNLOADS_SHOWN: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Status {
+    Pending,
+    Running,
+    Ok,
+    Failed,
+    Interrupted,
+}
+
+impl From<StepStatus> for Status {
+    fn from(status: StepStatus) -> Self {
+        match status {
+            StepStatus::Pending => Self::Pending,
+            StepStatus::Ok => Self::Ok,
+            StepStatus::Failed => Self::Failed,
+            StepStatus::Interrupted => Self::Interrupted,
+        }
+    }
+}
+
+impl Status {
+    fn symbol(&self) -> &'static str {
+        match self {
+            Self::Pending => "·",
+            Self::Running => "▶",
+            Self::Ok => "✓",
+            Self::Failed => "✗",
+            Self::Interrupted => "!",
+        }
+    }
+
+    fn color(&self) -> Color {
+        match self {
+            Self::Pending => Color::DarkGray,
+            Self::Running => Color::Yellow,
+            Self::Ok => Color::Green,
+            Self::Failed | Self::Interrupted => Color::Red,
+        }
+    }
+}
+
+/// Элемент очереди: собираемый пакет или загружаемый файл
+#[derive(Debug, Clone)]
+pub struct Item {
+    /// `stage/name` для пакетов, имя файла для загрузок
+    pub key: String,
+    pub version: String,
+    pub log: Option<PathBuf>,
+
+    /// Ожидаемая длительность (в секундах) по истории сборок
+    pub expected: Option<f64>,
+
+    status: Status,
+    started: Option<Instant>,
+    duration: Option<f64>,
+}
+
+impl Item {
+    pub fn new(key: String, version: String, log: Option<PathBuf>, expected: Option<f64>) -> Self {
+        Self {
+            key,
+            version,
+            log,
+            expected,
+            status: Status::Pending,
+            started: None,
+            duration: None,
+        }
+    }
+
+    fn elapsed(&self) -> Option<f64> {
+        match self.status {
+            Status::Running => self.started.map(|t| t.elapsed().as_secs_f64()),
+            _ => self.duration,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Download {
+    url: String,
+    file: String,
+    downloaded: u64,
+    total: Option<u64>,
+    done: bool,
+}
+
+#[derive(Debug)]
+struct State {
+    title: String,
+    items: Vec<Item>,
+    current: Option<usize>,
+
+    /// Текущий этап работы (последнее событие `stage`)
+    phase: String,
+    downloads: Vec<Download>,
+    messages: Vec<String>,
+    started: Instant,
+}
+
+impl State {
+    fn find(&self, key: &str) -> Option<usize> {
+        self.items.iter().position(|item| item.key == key)
+    }
+
+    fn start_item(&mut self, key: &str) {
+        if let Some(i) = self.find(key) {
+            self.items[i].status = Status::Running;
+            self.items[i].started = Some(Instant::now());
+            self.current = Some(i);
+        }
+    }
+
+    fn finish_item(&mut self, key: &str, status: Status, duration: Option<f64>) {
+        if let Some(i) = self.find(key) {
+            let item = &mut self.items[i];
+            item.duration = duration.or(item.started.map(|t| t.elapsed().as_secs_f64()));
+            item.status = status;
+            if self.current == Some(i) {
+                self.current = None;
+            }
+        }
+    }
+
+    fn handle(&mut self, event: &Event) {
+        match event {
+            Event::Stage { message } => {
+                self.phase = message.trim_end_matches('.').to_string();
+            }
+            Event::Info { message } => self.messages.push(message.clone()),
+            Event::Warning { message } => self
+                .messages
+                .push(format!("{}: {message}", tr!("tui.warning"))),
+            Event::DownloadStarted { url, file, total } => {
+                self.downloads.push(Download {
+                    url: url.to_string(),
+                    file: file.to_string(),
+                    downloaded: 0,
+                    total: *total,
+                    done: false,
+                });
+                self.start_item(file);
+            }
+            Event::DownloadProgress {
+                url, downloaded, ..
+            } => {
+                if let Some(dl) = self.downloads.iter_mut().rev().find(|d| d.url == *url) {
+                    dl.downloaded = *downloaded;
+                }
+            }
+            Event::DownloadFinished { url, file, cached } => {
+                match self.downloads.iter_mut().rev().find(|d| d.url == *url) {
+                    Some(dl) => dl.done = true,
+                    None if *cached => self.start_item(file),
+                    None => {}
+                }
+            }
+            Event::Checksum { file, ok, .. } => {
+                let status = if *ok { Status::Ok } else { Status::Failed };
+                self.finish_item(file, status, None);
+            }
+            Event::PackageStarted { stage, package, .. } => {
+                self.start_item(&format!("{stage}/{package}"));
+            }
+            Event::PackageFinished {
+                stage,
+                package,
+                status,
+                duration,
+                ..
+            } => {
+                self.finish_item(
+                    &format!("{stage}/{package}"),
+                    (*status).into(),
+                    Some(*duration),
+                );
+            }
+            _ => {}
+        }
+    }
+
+    /// Оставшееся время (в секундах) и признак того, что для части пакетов
+    /// оценки нет
+    fn eta(&self) -> Option<(f64, bool)> {
+        let mut total = 0.0;
+        let mut known = false;
+        let mut unknown = false;
+
+        for item in &self.items {
+            match (item.status, item.expected) {
+                (Status::Pending, Some(exp)) => total += exp,
+                (Status::Running, Some(exp)) => {
+                    total += (exp - item.elapsed().unwrap_or(0.0)).max(0.0)
+                }
+                (Status::Pending | Status::Running, None) => unknown = true,
+                _ => continue,
+            }
+            known |= item.expected.is_some();
+        }
+
+        known.then_some((total, unknown))
+    }
+}
+
+/// Просмотр журнала пакета
+#[derive(Debug)]
+struct LogView {
+    item: usize,
+    lines: Vec<String>,
+
+    /// Размер файла при последнем чтении
+    len: u64,
+
+    /// Первая видимая строка
+    offset: usize,
+
+    /// Следовать за концом журнала
+    follow: bool,
+
+    /// Число видимых строк при последней отрисовке
+    height: usize,
+}
+
+impl LogView {
+    fn new(item: usize) -> Self {
+        Self {
+            item,
+            lines: Vec::new(),
+            len: u64::MAX,
+            offset: 0,
+            follow: true,
+            height: 1,
+        }
+    }
+
+    /// Перечитывает журнал, если он изменился
+    fn reload(&mut self, pth: &Path) {
+        let len = fs::metadata(pth).map_or(0, |m| m.len());
+        if len != self.len {
+            self.len = len;
+            self.lines = fs::read(pth)
+                .map(|data| String::from_utf8_lossy(&data).lines().map(clean).collect())
+                .unwrap_or_default();
+        }
+    }
+
+    fn max_offset(&self) -> usize {
+        self.lines.len().saturating_sub(self.height)
+    }
+
+    fn scroll(&mut self, delta: isize) {
+        let offset = if self.follow {
+            self.max_offset()
+        } else {
+            self.offset
+        };
+        self.offset = offset.saturating_add_signed(delta).min(self.max_offset());
+        self.follow = self.offset == self.max_offset();
+    }
+}
+
+/// Полноэкранная панель. Закрывается при уничтожении, после чего выводит
+/// итоги в построчном режиме.
+pub struct Dashboard {
+    state: Arc<Mutex<State>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Dashboard {
+    /// Открывает панель. Возвращает `None`, если терминал не интерактивный
+    /// или включён режим JSON.
+    pub fn start(title: &str, items: Vec<Item>) -> Result<Option<Self>> {
+        if is_json() || !stdout().is_terminal() || !stdin().is_terminal() {
+            return Ok(None);
+        }
+
+        let state = Arc::new(Mutex::new(State {
+            title: title.to_string(),
+            items,
+            current: None,
+            phase: String::new(),
+            downloads: Vec::new(),
+            messages: Vec::new(),
+            started: Instant::now(),
+        }));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        restore_on_panic();
+        let terminal = ratatui::try_init()?;
+        let listener = state.clone();
+        events::set_listener(Some(Box::new(move |event| lock(&listener).handle(event))));
+
+        let thread = thread::spawn({
+            let (state, stop) = (state.clone(), stop.clone());
+            move || run(terminal, &state, &stop)
+        });
+
+        Ok(Some(Self {
+            state,
+            stop,
+            thread: Some(thread),
+        }))
+    }
+}
+
+impl Drop for Dashboard {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        events::set_listener(None);
+
+        let state = lock(&self.state);
+        for item in state.items.iter().filter(|i| i.status != Status::Pending) {
+            let symbol = match item.status {
+                Status::Ok => Colorize::green(item.status.symbol()),
+                Status::Running => Colorize::yellow(item.status.symbol()),
+                _ => Colorize::red(item.status.symbol()),
+            };
+            let duration = item.elapsed().map(format_duration).unwrap_or_default();
+            println!(
+                "  {symbol} {} {} {}",
+                &item.key,
+                item.version.dimmed(),
+                duration.dimmed()
+            );
+        }
+        for msg in &state.messages {
+            println!("{msg}");
+        }
+    }
+}
+
+/// Восстанавливает терминал при панике в любом потоке. Сборка release
+/// использует `panic = "abort"`, поэтому `Drop` панели при панике не
+/// выполняется и терминал остался бы в raw-режиме на альтернативном экране.
+fn restore_on_panic() {
+    static HOOK: Once = Once::new();
+    HOOK.call_once(|| {
+        let hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            ratatui::restore();
+            hook(info);
+        }));
+    });
+}
+
+fn lock(state: &Mutex<State>) -> MutexGuard<'_, State> {
+    state.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn run(mut terminal: DefaultTerminal, state: &Mutex<State>, stop: &AtomicBool) {
+    let mut list = ListState::default().with_selected(Some(0));
+    let mut follow = true;
+    let mut log_view: Option<LogView> = None;
+
+    while !stop.load(Ordering::SeqCst) {
+        {
+            let state = lock(state);
+            if follow {
+                list.select(state.current.or(list.selected()));
+            }
+            let _ = terminal.draw(|frame| draw(frame, &state, &mut list, log_view.as_mut()));
+        }
+
+        if !event::poll(FRAME_INTERVAL).unwrap_or(false) {
+            continue;
+        }
+        let Ok(Input::Key(key)) = event::read() else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+
+        // в raw-режиме терминал не отправляет SIGINT. Панель закрывается, а
+        // события продолжают поступать до её уничтожения, чтобы в итогах
+        // было состояние прерванного пакета.
+        if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+            ratatui::restore();
+            // SAFETY: вызов не затрагивает память процесса
+            unsafe { libc::raise(libc::SIGINT) };
+            return;
+        }
+
+        match log_view.as_mut() {
+            Some(view) => match key.code {
+                KeyCode::Esc | KeyCode::Char('q') => log_view = None,
+                code => scroll_log(view, code),
+            },
+            None => handle_queue_key(key, &mut list, &mut follow, &mut log_view),
+        }
+    }
+
+    ratatui::restore();
+}
+
+fn handle_queue_key(
+    key: KeyEvent,
+    list: &mut ListState,
+    follow: &mut bool,
+    log_view: &mut Option<LogView>,
+) {
+    match key.code {
+        KeyCode::Up | KeyCode::Char('k') => {
+            *follow = false;
+            list.select_previous();
+        }
+        KeyCode::Down | KeyCode::Char('j') => {
+            *follow = false;
+            list.select_next();
+        }
+        KeyCode::Char('f') => *follow = true,
+        KeyCode::Enter => *log_view = list.selected().map(LogView::new),
+        _ => {}
+    }
+}
+
+fn scroll_log(view: &mut LogView, code: KeyCode) {
+    let page = view.height as isize;
+    match code {
+        KeyCode::Up | KeyCode::Char('k') => view.scroll(-1),
+        KeyCode::Down | KeyCode::Char('j') => view.scroll(1),
+        KeyCode::PageUp => view.scroll(-page),
+        KeyCode::PageDown | KeyCode::Char(' ') => view.scroll(page),
+        KeyCode::Home | KeyCode::Char('g') => {
+            view.follow = false;
+            view.offset = 0;
+        }
+        KeyCode::End | KeyCode::Char('G') => view.follow = true,
+        _ => {}
+    }
+}
+
+fn draw(frame: &mut Frame, state: &State, list: &mut ListState, log_view: Option<&mut LogView>) {
+    let downloads = state
+        .downloads
+        .iter()
+        .filter(|d| !d.done)
+        .take(DOWNLOADS_SHOWN)
+        .collect::<Vec<_>>();
+    let messages = &state.messages[state.messages.len().saturating_sub(MESSAGES_SHOWN)..];
+
+    let [header, body, dl_area, msg_area, footer] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Min(5),
+        Constraint::Length(downloads.len() as u16),
+        Constraint::Length(messages.len() as u16),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    frame.render_widget(Paragraph::new(header_line(state)), header);
+
+    match log_view {
+        Some(view) => draw_log(frame, body, state, view),
+        None => {
+            let [queue, current] =
+                Layout::horizontal([Constraint::Percentage(35), Constraint::Min(20)]).areas(body);
+            draw_queue(frame, queue, state, list);
+            draw_current(frame, current, state, list.selected());
+        }
+    }
+
+    for (dl, area) in downloads.iter().zip(dl_area.rows()) {
+        let (ratio, label) = match dl.total {
+            Some(total) if total > 0 => (
+                (dl.downloaded as f64 / total as f64).min(1.0),
+                format!("{} {}%", &dl.file, dl.downloaded * 100 / total),
+            ),
+            _ => (0.0, format!("{} {} KiB", &dl.file, dl.downloaded / 1024)),
+        };
+        let gauge = Gauge::default()
+            .gauge_style(Style::new().fg(Color::Cyan))
+            .ratio(ratio)
+            .label(label);
+        frame.render_widget(gauge, area);
+    }
+
+    let msgs = messages
+        .iter()
+        .map(|m| Line::from(m.as_str()).fg(Color::Yellow))
+        .collect::<Vec<_>>();
+    frame.render_widget(Paragraph::new(msgs), msg_area);
+
+    let keys = match frame.area().width {
+        0..60 => "↑↓ Enter Esc ^C".to_string(),
+        _ => tr!("dashboard.keys"),
+    };
+    frame.render_widget(Paragraph::new(keys).fg(Color::DarkGray), footer);
+}
+
+fn header_line(state: &State) -> Line<'static> {
+    let done = state
+        .items
+        .iter()
+        .filter(|i| !matches!(i.status, Status::Pending | Status::Running))
+        .count();
+    let eta = match state.eta() {
+        Some((eta, true)) => format!(">{}", format_duration(eta)),
+        Some((eta, false)) => format_duration(eta),
+        None => "-".to_string(),
+    };
+
+    Line::from(vec![
+        Span::from(format!(" ALFA {} ", &state.title))
+            .bold()
+            .reversed(),
+        Span::from(format!(
+            "  {}",
+            tr!(
+                "dashboard.progress",
+                done = done,
+                total = state.items.len(),
+                elapsed = format_duration(state.started.elapsed().as_secs_f64()),
+                eta = eta
+            )
+        )),
+    ])
+}
+
+fn draw_queue(frame: &mut Frame, area: Rect, state: &State, list: &mut ListState) {
+    let items = state
+        .items
+        .iter()
+        .map(|item| {
+            let duration = item.elapsed().map(format_duration).unwrap_or_default();
+            ListItem::new(Line::from(vec![
+                Span::from(format!("{} ", item.status.symbol())).fg(item.status.color()),
+                Span::from(item.key.clone()),
+                Span::from(format!(" {duration}")).fg(Color::DarkGray),
+            ]))
+        })
+        .collect::<Vec<_>>();
+
+    let widget = List::new(items)
+        .block(Block::bordered().title(format!(" {} ", tr!("dashboard.queue"))))
+        .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+    frame.render_stateful_widget(widget, area, list);
+}
+
+/// Собираемый (или выбранный) пакет и последние строки его журнала
+fn draw_current(frame: &mut Frame, area: Rect, state: &State, selected: Option<usize>) {
+    let item = state.current.or(selected).and_then(|i| state.items.get(i));
+
+    let title = match item {
+        Some(item) if state.phase.is_empty() => format!(" {} {} ", &item.key, &item.version),
+        Some(item) => format!(" {} {} - {} ", &item.key, &item.version, &state.phase),
+        None => format!(" {} ", &state.phase),
+    };
+    let block = Block::bordered().title(title);
+    let height = block.inner(area).height as usize;
+
+    let lines = item
+        .and_then(|item| item.log.as_deref())
+        .map(|pth| tail(pth, height))
+        .unwrap_or_default()
+        .into_iter()
+        .map(Line::from)
+        .collect::<Vec<_>>();
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+fn draw_log(frame: &mut Frame, area: Rect, state: &State, view: &mut LogView) {
+    let Some(item) = state.items.get(view.item) else {
+        return;
+    };
+    let block = Block::bordered().title(format!(" {} {} ", &item.key, &item.version));
+    view.height = (block.inner(area).height as usize).max(1);
+
+    let lines = match &item.log {
+        Some(pth) => {
+            view.reload(pth);
+            if view.follow {
+                view.offset = view.max_offset();
+            }
+            view.lines
+                .iter()
+                .skip(view.offset)
+                .take(view.height)
+                .map(|l| Line::from(l.as_str()))
+                .collect()
+        }
+        None => vec![Line::from(tr!("dashboard.no_log")).fg(Color::DarkGray)],
+    };
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+/// Последние `count` строк файла
+fn tail(pth: &Path, count: usize) -> Vec<String> {
+    let mut data = Vec::new();
+    let rslt = File::open(pth).and_then(|mut file| {
+        let len = file.metadata()?.len();
+        file.seek(SeekFrom::Start(len.saturating_sub(TAIL_BYTES)))?;
+        file.read_to_end(&mut data)
+    });
+    if rslt.is_err() {
+        return Vec::new();
+    }
+
+    let text = String::from_utf8_lossy(&data);
+    let lines = text.lines().collect::<Vec<_>>();
+    lines[lines.len().saturating_sub(count)..]
+        .iter()
+        .map(|l| clean(l))
+        .collect()
+}
+
+/// Убирает из строки журнала управляющие символы (escape-последовательности,
+/// `\r` от индикаторов выполнения)
+fn clean(line: &str) -> String {
+    let line = line.rsplit('\r').next().unwrap_or(line);
+    line.replace('\t', "    ")
+        .chars()
+        .filter(|c| !c.is_control())
+        .collect()
+}
@@ -8,11 +8,21 @@ use md5::compute;
 use reqwest::Client;
 use std::{
     cmp::min,
-    fs::{read, File},
+    fs::{read, remove_file, File},
     io::Write,
     path::Path,
+    time::{Duration, Instant},
 };
 
+use crate::error::AlfaError;
+use crate::events::{emit, is_human, Event};
+use crate::interrupt;
+use crate::logging::{self, console, Level};
+use crate::tr;
+
+/// Минимальный интервал между событиями `download_progress`
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
 #[tokio::main]
 pub async fn download<U, P>(client: &Client, url: U, path: Option<P>, prefix: P) -> Result<()>
 where
@@ -20,34 +30,63 @@ where
     P: AsRef<Path>,
 {
     let disp_url = url.to_string();
-    let res = client
-        .get(url.to_string())
-        .send()
-        .await
-        .or(Err(Error::msg(format!(
-            "Failed to GET from '{}'",
-            &disp_url.dimmed(),
-        ))))?;
-
     let path = match path {
         Some(p) => p.as_ref().display().to_string(),
-        None => disp_url
-            .rsplit_once('/')
-            .unwrap_or(("tmp.bin", "tmp.bin"))
-            .1
-            .to_string(),
+        None => file_name(&disp_url).to_string(),
     };
     let prefix = prefix.as_ref();
 
     let fpth = prefix.join(&path);
     if fpth.exists() {
-        println!("File '{}' is already downloaded.", &path.dimmed());
+        logging::info(tr!(
+            "download.cached",
+            url = &disp_url,
+            path = fpth.display()
+        ));
+        if !is_human() {
+            emit(&Event::DownloadFinished {
+                url: &disp_url,
+                file: &path,
+                cached: true,
+            });
+        } else if console(Level::Info) {
+            println!(
+                "{}",
+                tr!("download.already_downloaded", file = path.dimmed())
+            );
+        }
         return Ok(());
     }
 
-    let total_size = res.content_length().unwrap_or(u64::MAX) / 1024;
+    logging::debug(tr!("download.get", url = &disp_url));
+    let res = client
+        .get(url.to_string())
+        .send()
+        .await
+        .and_then(|res| res.error_for_status())
+        .map_err(|why| AlfaError::Download {
+            url: disp_url.clone(),
+            reason: why.to_string(),
+        })?;
 
-    let pb = ProgressBar::new(total_size);
+    let total = res.content_length();
+    logging::info(tr!(
+        "download.start",
+        url = &disp_url,
+        path = fpth.display(),
+        size = total.map_or(tr!("download.unknown_size"), |t| t.to_string())
+    ));
+    let total_size = total.unwrap_or(u64::MAX) / 1024;
+    emit(&Event::DownloadStarted {
+        url: &disp_url,
+        file: &path,
+        total,
+    });
+
+    let pb = match console(Level::Info) {
+        true => ProgressBar::new(total_size),
+        false => ProgressBar::hidden(),
+    };
     pb.set_style(
         ProgressStyle::default_bar()
             .template("{msg} [{bar:20}] {percent}% {elapsed}/{eta} {bytes_per_sec}")?
@@ -55,34 +94,121 @@ where
     );
     let hdr = format!(
         "{:<width$}",
-        format!("Downloading '{}'", compress_name(&path, 20).dimmed()),
+        tr!(
+            "download.downloading",
+            file = compress_name(&path, 20).dimmed()
+        ),
         width = 45
     );
     pb.set_message(hdr);
 
-    let mut file = File::create(fpth)?;
+    let mut file = File::create(&fpth)?;
     let mut downloaded: u64 = 0;
+    let mut downloaded_bytes: u64 = 0;
+    let mut reported = Instant::now();
     let mut stream = res.bytes_stream();
 
     while let Some(item) = stream.next().await {
-        let chunk = item?;
+        // недокачанный файл удаляется, чтобы при повторном запуске он не
+        // считался уже загруженным
+        let chunk = match item {
+            Ok(chunk) if !interrupt::interrupted() => chunk,
+            Ok(_) => {
+                pb.abandon();
+                let _ = remove_file(&fpth);
+                return Err(AlfaError::Interrupted.into());
+            }
+            Err(why) => {
+                pb.abandon();
+                let _ = remove_file(&fpth);
+                return Err(AlfaError::Download {
+                    url: disp_url,
+                    reason: why.to_string(),
+                }
+                .into());
+            }
+        };
         file.write_all(&chunk)?;
         let new = min(downloaded + (chunk.len() as u64 / 1024), total_size);
         downloaded = new;
         pb.set_position(new);
+
+        downloaded_bytes += chunk.len() as u64;
+        if reported.elapsed() >= PROGRESS_INTERVAL {
+            reported = Instant::now();
+            logging::trace(format!("'{disp_url}': {downloaded_bytes} bytes"));
+            emit(&Event::DownloadProgress {
+                url: &disp_url,
+                downloaded: downloaded_bytes,
+                total,
+            });
+        }
     }
     pb.finish();
+    logging::info(tr!(
+        "download.finished",
+        url = &disp_url,
+        size = downloaded_bytes
+    ));
+    emit(&Event::DownloadFinished {
+        url: &disp_url,
+        file: &path,
+        cached: false,
+    });
 
     Ok(())
 }
 
+/// Загружает файл в директорию `prefix`: сначала пытается загрузить его с
+/// зеркал (`<mirror>/<file name>`), затем по исходному адресу `url`
+pub fn download_any<P: AsRef<Path>>(
+    client: &Client,
+    url: &str,
+    mirrors: &[String],
+    prefix: P,
+) -> Result<()> {
+    let fname = file_name(url);
+    let mut urls = mirrors
+        .iter()
+        .map(|mirror| format!("{}/{fname}", mirror.trim_end_matches('/')))
+        .collect::<Vec<_>>();
+    urls.push(url.to_string());
+
+    let mut last_err = None;
+    for url in urls {
+        match download(client, &url, None, prefix.as_ref()) {
+            Ok(()) => return Ok(()),
+            // остальные зеркала не опрашиваются
+            Err(why) if interrupt::interrupted() => return Err(why),
+            Err(why) => {
+                logging::warn(&why);
+                if !is_human() {
+                    emit(&Event::Warning {
+                        message: why.to_string(),
+                    });
+                } else if console(Level::Warn) {
+                    println!("{}: {why}", tr!("tui.warning").bold().yellow());
+                }
+                last_err = Some(why);
+            }
+        }
+    }
+
+    Err(last_err.unwrap_or(Error::msg(tr!("download.no_urls"))))
+}
+
+/// Имя файла из адреса (последний компонент пути)
+pub fn file_name(url: &str) -> &str {
+    url.rsplit_once('/').map_or("tmp.bin", |(_, name)| name)
+}
+
 fn compress_name(name: &str, size_max: usize) -> String {
     let len = name.len() - 1;
     if size_max > len {
         return name.to_string();
     }
-    let mut unneeded_chars = if len > size_max { 0 } else { size_max - len };
-    if unneeded_chars % 2 == 0 {
+    let mut unneeded_chars = size_max.saturating_sub(len);
+    if unneeded_chars.is_multiple_of(2) {
         unneeded_chars /= 2;
     }
     unneeded_chars += 1;
@@ -132,5 +258,5 @@ pub fn check_md5<P: AsRef<Path>>(pth: P, md5: &str) -> Result<bool> {
     let digest = compute(&data);
     let a = format!("{:?}", digest);
 
-    Ok(&a == md5)
+    Ok(a == md5)
 }
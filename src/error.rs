@@ -0,0 +1,154 @@
+//! Typed errors and process exit codes
+//!
+//! Функции библиотеки возвращают `anyhow::Result`. Ошибки, по которым
+//! автоматизации нужно принимать решения (сбой сети, ошибка в инструкции и
+//! т.д.), создаются из [`AlfaError`] и могут быть получены из
+//! `anyhow::Error` через `downcast_ref`. Каждой категории ошибок соответствует
+//! код завершения процесса `alfa`:
+//!
+//! | Код | Категория |
+//! | --- | --- |
+//! | 1 | прочие ошибки |
+//! | 2 | неверные аргументы командной строки |
+//! | 3 | неверная конфигурация |
+//! | 4 | отсутствующая или неверная сборочная инструкция |
+//! | 5 | недостаточно прав |
+//! | 6 | ошибка загрузки |
+//! | 7 | несовпадение контрольной суммы |
+//! | 8 | ошибка сборочного скрипта |
+//! | 130 | сборка прервана |
+//!
+//! Сообщения об ошибках не содержат escape-последовательностей терминала.
+
+use std::{fmt, io, path::PathBuf};
+
+use crate::tr;
+
+#[derive(Debug)]
+pub enum AlfaError {
+    /// Файл не удалось загрузить ни с одного из адресов
+    Download { url: String, reason: String },
+
+    /// Контрольная сумма файлов не совпала с указанной в `packages.toml`
+    Checksum { files: Vec<String> },
+
+    /// Файл сборочной инструкции не найден
+    MissingInstruction { path: PathBuf },
+
+    /// Файл сборочной инструкции не удалось разобрать
+    InvalidInstruction { path: PathBuf, reason: String },
+
+    /// Неверная или отсутствующая конфигурация (`.config.toml`, профиль,
+    /// профиль платы, переопределения)
+    Config(String),
+
+    /// Сборочный скрипт завершился с ошибкой
+    Script {
+        name: String,
+        status: String,
+        log: PathBuf,
+    },
+
+    /// Работа прервана (SIGINT, SIGTERM)
+    Interrupted,
+
+    /// Операция требует прав, которых у процесса нет
+    Privilege(String),
+}
+
+impl AlfaError {
+    /// Код завершения процесса `alfa`
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Self::Config(_) => 3,
+            Self::MissingInstruction { .. } | Self::InvalidInstruction { .. } => 4,
+            Self::Privilege(_) => 5,
+            Self::Download { .. } => 6,
+            Self::Checksum { .. } => 7,
+            Self::Script { .. } => 8,
+            Self::Interrupted => 130,
+        }
+    }
+
+    /// Категория ошибки (поле `kind` события `error`)
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Download { .. } => "download",
+            Self::Checksum { .. } => "checksum",
+            Self::MissingInstruction { .. } => "missing_instruction",
+            Self::InvalidInstruction { .. } => "invalid_instruction",
+            Self::Config(_) => "config",
+            Self::Script { .. } => "script",
+            Self::Interrupted => "interrupted",
+            Self::Privilege(_) => "privilege",
+        }
+    }
+}
+
+impl fmt::Display for AlfaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg = match self {
+            Self::Download { url, reason } => tr!("error.download", url = url, reason = reason),
+            Self::Checksum { files } => tr!(
+                "error.checksum",
+                files = files
+                    .iter()
+                    .map(|file| format!("'{file}'"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Self::MissingInstruction { path } => {
+                tr!("error.missing_instruction", path = path.display())
+            }
+            Self::InvalidInstruction { path, reason } => tr!(
+                "error.invalid_instruction",
+                path = path.display(),
+                reason = reason
+            ),
+            Self::Config(msg) | Self::Privilege(msg) => msg.clone(),
+            Self::Script { name, status, log } => tr!(
+                "error.script",
+                name = name,
+                status = status,
+                log = log.display()
+            ),
+            Self::Interrupted => tr!("error.interrupted"),
+        };
+        write!(f, "{msg}")
+    }
+}
+
+impl std::error::Error for AlfaError {}
+
+/// Ищет [`AlfaError`] в цепочке причин ошибки
+fn find(err: &anyhow::Error) -> Option<&AlfaError> {
+    err.chain()
+        .find_map(|cause| cause.downcast_ref::<AlfaError>())
+}
+
+/// Отказ в доступе со стороны ОС считается ошибкой прав
+fn is_permission_denied(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        cause
+            .downcast_ref::<io::Error>()
+            .is_some_and(|e| e.kind() == io::ErrorKind::PermissionDenied)
+    })
+}
+
+/// Код завершения процесса для ошибки
+pub fn exit_code(err: &anyhow::Error) -> u8 {
+    match find(err) {
+        Some(e) => e.exit_code(),
+        None if is_permission_denied(err) => 5,
+        None => 1,
+    }
+}
+
+/// Категория ошибки; `other` для ошибок без категории
+pub fn kind(err: &anyhow::Error) -> &'static str {
+    match find(err) {
+        Some(e) => e.kind(),
+        None if is_permission_denied(err) => "privilege",
+        None => "other",
+    }
+}
@@ -0,0 +1,194 @@
+//! Machine-readable output (`--message-format json`)
+//!
+//! В режиме JSON вместо текстовых сообщений в stdout выводятся события - по
+//! одному JSON-объекту на строку (NDJSON). Каждое событие содержит поле
+//! `event` с типом события и поле `time` (UNIX time); остальные поля зависят
+//! от типа события и не меняются между версиями ALFA.
+//!
+//! События также передаются подписчику (см. [`set_listener`]), например,
+//! панели сборки (`alfa::dashboard`). Пока подписчик установлен, текстовые
+//! сообщения не выводятся.
+
+use clap::ValueEnum;
+use serde::Serialize;
+use std::{
+    io::{stdout, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+use crate::build_meta::StepStatus;
+use crate::host::Check;
+use crate::pkginfo::PackageEntry;
+use crate::timing::unix_time;
+use crate::upstream::Outdated;
+
+static JSON: AtomicBool = AtomicBool::new(false);
+
+type Listener = Box<dyn Fn(&Event) + Send>;
+
+static LISTENER: Mutex<Option<Listener>> = Mutex::new(None);
+static LISTENING: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum MessageFormat {
+    /// Текстовые сообщения для человека
+    #[default]
+    Human,
+
+    /// События в формате NDJSON
+    Json,
+}
+
+pub fn set_message_format(format: MessageFormat) {
+    JSON.store(format == MessageFormat::Json, Ordering::SeqCst);
+    // сообщения об ошибках в событиях не должны содержать escape-последовательности
+    if format == MessageFormat::Json {
+        colored::control::set_override(false);
+    }
+}
+
+/// Выводятся ли события вместо текстовых сообщений
+pub fn is_json() -> bool {
+    JSON.load(Ordering::SeqCst)
+}
+
+/// Выводятся ли текстовые сообщения (не включён режим JSON и не установлен
+/// подписчик на события)
+pub fn is_human() -> bool {
+    !is_json() && !LISTENING.load(Ordering::SeqCst)
+}
+
+/// Устанавливает (или снимает) подписчика на события
+pub fn set_listener(listener: Option<Listener>) {
+    let mut lock = LISTENER.lock().unwrap_or_else(|e| e.into_inner());
+    LISTENING.store(listener.is_some(), Ordering::SeqCst);
+    *lock = listener;
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event<'a> {
+    /// Начало очередного этапа работы (`==> ...` в текстовом режиме)
+    Stage {
+        message: String,
+    },
+    Info {
+        message: String,
+    },
+    Warning {
+        message: String,
+    },
+
+    /// Результат проверки хост-системы или свободного места
+    Check(&'a Check),
+
+    ConfigValue {
+        key: &'a str,
+        value: &'a toml::Value,
+
+        #[serde(skip_serializing_if = "Option::is_none")]
+        origin: Option<&'a str>,
+    },
+    Config {
+        value: &'a toml::Value,
+    },
+
+    DownloadStarted {
+        url: &'a str,
+        file: &'a str,
+
+        /// Размер файла (в байтах), если он известен
+        total: Option<u64>,
+    },
+    DownloadProgress {
+        url: &'a str,
+        downloaded: u64,
+        total: Option<u64>,
+    },
+    DownloadFinished {
+        url: &'a str,
+        file: &'a str,
+
+        /// Файл был загружен ранее
+        cached: bool,
+    },
+    Checksum {
+        file: &'a str,
+        expected: &'a str,
+        ok: bool,
+    },
+    ScriptGenerated {
+        package: &'a str,
+        version: &'a str,
+        path: String,
+    },
+    PackageStarted {
+        stage: &'a str,
+        package: &'a str,
+        version: &'a str,
+        log: String,
+    },
+    PackageFinished {
+        stage: &'a str,
+        package: &'a str,
+        version: &'a str,
+        status: StepStatus,
+
+        /// Длительность сборки (в секундах)
+        duration: f64,
+        sbu: Option<f64>,
+        log: String,
+    },
+    /// Сведения о пакете (`alfa list`, `alfa info`, `alfa search`)
+    Package(&'a PackageEntry<'a>),
+
+    /// Результат проверки новых версий пакета (`alfa outdated`)
+    Upstream(&'a Outdated),
+
+    Report {
+        format: &'a str,
+        content: String,
+    },
+    Error {
+        message: String,
+
+        /// Категория ошибки (см. `alfa::error`)
+        kind: &'a str,
+
+        /// Код завершения процесса
+        code: u8,
+    },
+}
+
+#[derive(Serialize)]
+struct Line<'a> {
+    time: u64,
+
+    #[serde(flatten)]
+    event: &'a Event<'a>,
+}
+
+/// Передаёт событие подписчику и выводит его, если включён режим JSON
+pub fn emit(event: &Event) {
+    if LISTENING.load(Ordering::SeqCst) {
+        let lock = LISTENER.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(listener) = lock.as_ref() {
+            listener(event);
+        }
+    }
+    if !is_json() {
+        return;
+    }
+    let line = Line {
+        time: unix_time(),
+        event,
+    };
+    if let Ok(json) = serde_json::to_string(&line) {
+        let mut out = stdout().lock();
+        let _ = writeln!(out, "{json}");
+        let _ = out.flush();
+    }
+}
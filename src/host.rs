@@ -0,0 +1,349 @@
+//! Host system prerequisites verification (`alfa check-host`)
+//!
+//! Аналог скрипта `version-check.sh` из руководства LFS: проверяет наличие и
+//! версии необходимых программ, версию ядра и объём свободного места.
+//! Минимальные требования описываются в файле `host.toml` в составе
+//! сборочных инструкций.
+
+use anyhow::{Error, Result};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Ordering,
+    ffi::CString,
+    fs,
+    io::Read,
+    mem::MaybeUninit,
+    os::unix::{ffi::OsStrExt, fs::MetadataExt},
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use crate::events::{emit, is_human, Event};
+use crate::logging::{self, console, Level};
+use crate::tr;
+use toml;
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct HostRequirements {
+    /// Минимальная версия ядра Linux
+    pub kernel: String,
+
+    /// Минимальный объём свободного места в директории сборки (в МиБ)
+    pub free_space: u64,
+
+    #[serde(default)]
+    pub tool: Vec<Tool>,
+
+    #[serde(default)]
+    pub link: Vec<Link>,
+}
+
+/// Программа, версия которой проверяется
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Tool {
+    pub name: String,
+
+    /// Команда, выводящая версию программы, e.g. `["bash", "--version"]`
+    pub command: Vec<String>,
+    pub min: String,
+}
+
+/// Символическая ссылка (или скрипт-обёртка), которая должна указывать на
+/// определённую программу, e.g. `/bin/sh` -> `bash`
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Link {
+    pub path: String,
+    pub target: String,
+}
+
+/// Результат одной проверки
+#[derive(Debug, Serialize)]
+pub struct Check {
+    pub name: String,
+    pub required: String,
+    pub found: Option<String>,
+    pub ok: bool,
+}
+
+impl HostRequirements {
+    pub fn read<P: AsRef<Path>>(pth: P) -> Result<Self> {
+        let contents = fs::read_to_string(&pth)?;
+        let data = toml::from_str(&contents)?;
+
+        Ok(data)
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, pth: P) -> Result<()> {
+        let contents = toml::to_string(&self)?;
+        fs::write(&pth, contents)?;
+
+        Ok(())
+    }
+
+    /// Выполняет все проверки. Свободное место проверяется в директории
+    /// `build_dir` (или в ближайшей существующей родительской директории).
+    pub fn check<P: AsRef<Path>>(&self, build_dir: P) -> Vec<Check> {
+        let mut checks = Vec::new();
+
+        let kernel = kernel_version();
+        checks.push(Check {
+            name: tr!("host.kernel"),
+            required: format!(">= {}", &self.kernel),
+            ok: kernel
+                .as_deref()
+                .is_some_and(|ver| cmp_versions(ver, &self.kernel) != Ordering::Less),
+            found: kernel,
+        });
+
+        let build_dir = build_dir.as_ref();
+        let free = free_space(build_dir).ok();
+        checks.push(Check {
+            name: tr!("host.free_space", path = build_dir.display()),
+            required: format!(">= {} MiB", self.free_space),
+            ok: free.is_some_and(|free| free >= self.free_space),
+            found: free.map(|free| format!("{free} MiB")),
+        });
+
+        for tool in &self.tool {
+            let ver = tool_version(&tool.command);
+            checks.push(Check {
+                name: tool.name.clone(),
+                required: format!(">= {}", &tool.min),
+                ok: ver
+                    .as_deref()
+                    .is_some_and(|ver| cmp_versions(ver, &tool.min) != Ordering::Less),
+                found: ver,
+            });
+        }
+
+        for link in &self.link {
+            let found = link_target(&link.path);
+            checks.push(Check {
+                name: link.path.clone(),
+                required: format!("-> {}", &link.target),
+                ok: found.as_deref().is_some_and(|f| f.contains(&link.target)),
+                found,
+            });
+        }
+
+        checks
+    }
+
+    /// Версии программ из списка `tool` (`None`, если программа не найдена)
+    pub fn tool_versions(&self) -> Vec<(String, Option<String>)> {
+        self.tool
+            .iter()
+            .map(|tool| (tool.name.clone(), tool_version(&tool.command)))
+            .collect()
+    }
+}
+
+/// Версия ядра Linux хост-системы
+pub fn kernel_version() -> Option<String> {
+    fs::read_to_string("/proc/sys/kernel/osrelease")
+        .ok()
+        .and_then(|rel| parse_version(&rel))
+}
+
+/// Название дистрибутива хост-системы (`PRETTY_NAME` из `/etc/os-release`)
+pub fn distro() -> Option<String> {
+    let contents = fs::read_to_string("/etc/os-release").ok()?;
+    contents.lines().find_map(|line| {
+        line.strip_prefix("PRETTY_NAME=")
+            .map(|name| name.trim_matches('"').to_string())
+    })
+}
+
+/// Печатает результаты проверок в виде таблицы. Возвращает `true`, если все
+/// проверки пройдены.
+pub fn print_checks(checks: &[Check]) -> bool {
+    for check in checks {
+        let level = if check.ok { Level::Info } else { Level::Warn };
+        logging::log(
+            level,
+            tr!(
+                "host.check_result",
+                name = &check.name,
+                required = &check.required,
+                found = check.found.clone().unwrap_or(tr!("host.nothing"))
+            ),
+        );
+    }
+
+    if !is_human() {
+        for check in checks {
+            emit(&Event::Check(check));
+        }
+        return checks.iter().all(|c| c.ok);
+    }
+    if !console(Level::Info) {
+        return checks.iter().all(|c| c.ok);
+    }
+
+    // ширина столбцов в символах: заголовки могут быть не в ASCII
+    let width = |s: &str| s.chars().count();
+    let (head_name, head_req, head_found, head_status) = (
+        tr!("host.check"),
+        tr!("host.required"),
+        tr!("host.found"),
+        tr!("host.status"),
+    );
+    let not_found = tr!("host.not_found");
+    let w_name = checks
+        .iter()
+        .map(|c| width(&c.name))
+        .max()
+        .unwrap_or(0)
+        .max(width(&head_name));
+    let w_req = checks
+        .iter()
+        .map(|c| width(&c.required))
+        .max()
+        .unwrap_or(0)
+        .max(width(&head_req));
+    let w_found = checks
+        .iter()
+        .map(|c| width(c.found.as_deref().unwrap_or(&not_found)))
+        .max()
+        .unwrap_or(0)
+        .max(width(&head_found));
+
+    println!(
+        "{}",
+        format!("{head_name:<w_name$}  {head_req:<w_req$}  {head_found:<w_found$}  {head_status}")
+            .bold()
+    );
+    for check in checks {
+        println!(
+            "{:<w_name$}  {:<w_req$}  {:<w_found$}  {}",
+            &check.name,
+            &check.required,
+            check.found.as_deref().unwrap_or(&not_found),
+            if check.ok {
+                tr!("tui.ok").bold().green()
+            } else {
+                tr!("tui.error").bold().red()
+            }
+        );
+    }
+
+    checks.iter().all(|c| c.ok)
+}
+
+fn tool_version(command: &[String]) -> Option<String> {
+    let (prog, args) = command.split_first()?;
+    let out = Command::new(prog)
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .ok()?;
+
+    // некоторые программы (e.g. bzip2) выводят версию в stderr
+    let text = format!(
+        "{}\n{}",
+        String::from_utf8_lossy(&out.stdout),
+        String::from_utf8_lossy(&out.stderr)
+    );
+    text.lines().find_map(parse_version)
+}
+
+/// Ищет в строке первую подстроку, похожую на номер версии (`N.N[.N...]`)
+pub fn parse_version(s: &str) -> Option<String> {
+    let mut candidates = s
+        .split(|c: char| !c.is_ascii_digit() && c != '.')
+        .map(|part| part.trim_matches('.'));
+
+    candidates
+        .find(|part| {
+            part.contains('.')
+                && part
+                    .split('.')
+                    .all(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+        })
+        .map(str::to_string)
+}
+
+/// Сравнивает номера версий покомпонентно (`2.10` > `2.9`)
+pub fn cmp_versions(a: &str, b: &str) -> Ordering {
+    let parse = |v: &str| {
+        v.split('.')
+            .map(|n| n.parse::<u64>().unwrap_or(0))
+            .collect::<Vec<_>>()
+    };
+    let (a, b) = (parse(a), parse(b));
+
+    for i in 0..a.len().max(b.len()) {
+        let ord = a.get(i).unwrap_or(&0).cmp(b.get(i).unwrap_or(&0));
+        if ord != Ordering::Equal {
+            return ord;
+        }
+    }
+
+    Ordering::Equal
+}
+
+/// Для символической ссылки возвращает путь, на который она указывает; для
+/// обычного файла (скрипта-обёртки) - его содержимое
+fn link_target(path: &str) -> Option<String> {
+    let meta = fs::symlink_metadata(path).ok()?;
+    if meta.file_type().is_symlink() {
+        return fs::canonicalize(path).ok().map(|p| p.display().to_string());
+    }
+
+    let mut head = Vec::new();
+    fs::File::open(path)
+        .ok()?
+        .take(4096)
+        .read_to_end(&mut head)
+        .ok()?;
+    let head = String::from_utf8_lossy(&head);
+
+    // e.g. `exec /usr/bin/bison -y "$@"` для `/usr/bin/yacc`
+    if head.starts_with("#!") {
+        head.lines()
+            .rfind(|line| !line.trim().is_empty())
+            .map(|line| format!("script: {}", line.trim()))
+    } else {
+        Some(path.to_string())
+    }
+}
+
+/// Свободное место (в МиБ) на файловой системе, содержащей `path` (или его
+/// ближайшую существующую родительскую директорию)
+pub fn free_space<P: AsRef<Path>>(path: P) -> Result<u64> {
+    let path = existing_ancestor(path.as_ref())?;
+
+    let cpath = CString::new(path.as_os_str().as_bytes())?;
+    let mut stat = MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: `cpath` - корректная C-строка, `stat` инициализируется
+    // функцией `statvfs` при успешном завершении
+    let rslt = unsafe { libc::statvfs(cpath.as_ptr(), stat.as_mut_ptr()) };
+    if rslt != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    let stat = unsafe { stat.assume_init() };
+
+    // типы полей зависят от платформы
+    #[allow(clippy::useless_conversion)]
+    let (bavail, frsize) = (u64::from(stat.f_bavail), u64::from(stat.f_frsize));
+
+    Ok(bavail * frsize / 1024 / 1024)
+}
+
+/// Возвращает `true`, если оба пути находятся на одной файловой системе
+pub fn same_fs<P: AsRef<Path>, Q: AsRef<Path>>(a: P, b: Q) -> Result<bool> {
+    let dev = |p: &Path| -> Result<u64> { Ok(fs::metadata(existing_ancestor(p)?)?.dev()) };
+
+    Ok(dev(a.as_ref())? == dev(b.as_ref())?)
+}
+
+fn existing_ancestor(mut path: &Path) -> Result<&Path> {
+    while !path.exists() {
+        path = path
+            .parent()
+            .ok_or(Error::msg(tr!("host.no_such_path", path = path.display())))?;
+    }
+
+    Ok(path)
+}
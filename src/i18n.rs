@@ -0,0 +1,251 @@
+//! Localization of user interface messages
+//!
+//! Сообщения хранятся в каталогах `locales/<lang>.toml`, встроенных в
+//! исполняемый файл. Ключ сообщения - путь к строке в каталоге (e.g.
+//! `prepare.create_user`), параметры подставляются вместо `{name}`. Если в
+//! каталоге выбранного языка строки нет, используется английский каталог,
+//! а при отсутствии и в нём - сам ключ.
+//!
+//! Язык выбирается параметром `--lang`, либо по переменным окружения
+//! `LC_ALL`, `LC_MESSAGES` и `LANG` (первая непустая).
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    env,
+    fmt::{self, Display},
+    sync::OnceLock,
+};
+
+static LANG: OnceLock<Lang> = OnceLock::new();
+static CATALOGS: OnceLock<HashMap<Lang, Catalog>> = OnceLock::new();
+
+type Catalog = HashMap<String, String>;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, ValueEnum)]
+pub enum Lang {
+    #[default]
+    En,
+    Ru,
+}
+
+impl Lang {
+    /// Все поддерживаемые языки
+    pub const ALL: [Self; 2] = [Self::En, Self::Ru];
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::En => "en",
+            Self::Ru => "ru",
+        }
+    }
+
+    /// Язык по значению локали (e.g. `ru_RU.UTF-8`)
+    pub fn from_locale(locale: &str) -> Option<Self> {
+        let code = locale.split(['_', '.', '@', '-']).next()?;
+        Self::ALL.into_iter().find(|lang| lang.code() == code)
+    }
+
+    /// Язык по переменным окружения `LC_ALL`, `LC_MESSAGES` и `LANG`
+    pub fn from_env() -> Self {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .into_iter()
+            .filter_map(|var| env::var(var).ok())
+            .find(|val| !val.is_empty())
+            .and_then(|val| Self::from_locale(&val))
+            .unwrap_or_default()
+    }
+
+    fn catalog_source(&self) -> &'static str {
+        match self {
+            Self::En => include_str!("../locales/en.toml"),
+            Self::Ru => include_str!("../locales/ru.toml"),
+        }
+    }
+}
+
+/// Устанавливает язык интерфейса (`None` - определить по окружению)
+pub fn set_lang(lang: Option<Lang>) {
+    let _ = LANG.set(lang.unwrap_or_else(Lang::from_env));
+}
+
+pub fn lang() -> Lang {
+    *LANG.get_or_init(Lang::from_env)
+}
+
+/// Язык, указанный параметром `--lang` в аргументах командной строки.
+/// Нужен до разбора аргументов, чтобы локализовать справку clap.
+pub fn lang_from_args<I: IntoIterator<Item = String>>(args: I) -> Option<Lang> {
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let val = match arg.strip_prefix("--lang") {
+            Some("") => args.next()?,
+            Some(val) => match val.strip_prefix('=') {
+                Some(val) => val.to_string(),
+                None => continue,
+            },
+            None if arg == "--" => return None,
+            None => continue,
+        };
+        return Lang::from_str(&val, true).ok();
+    }
+    None
+}
+
+fn flatten(prefix: &str, table: toml::Table, catalog: &mut Catalog) {
+    for (key, val) in table {
+        let key = match prefix {
+            "" => key,
+            _ => format!("{prefix}.{key}"),
+        };
+        match val {
+            toml::Value::Table(table) => flatten(&key, table, catalog),
+            toml::Value::String(s) => {
+                catalog.insert(key, s);
+            }
+            val => {
+                catalog.insert(key, val.to_string());
+            }
+        }
+    }
+}
+
+fn catalogs() -> &'static HashMap<Lang, Catalog> {
+    CATALOGS.get_or_init(|| {
+        Lang::ALL
+            .into_iter()
+            .map(|lang| {
+                // каталоги встроены в исполняемый файл и проверяются при
+                // разработке, поэтому ошибка разбора - ошибка сборки ALFA
+                let table = toml::from_str(lang.catalog_source())
+                    .unwrap_or_else(|why| panic!("invalid '{}' catalog: {why}", lang.code()));
+                let mut catalog = Catalog::new();
+                flatten("", table, &mut catalog);
+                (lang, catalog)
+            })
+            .collect()
+    })
+}
+
+/// Строка каталога без подстановки параметров
+pub fn lookup(key: &str) -> Option<&'static str> {
+    let catalogs = catalogs();
+    catalogs[&lang()]
+        .get(key)
+        .or_else(|| catalogs[&Lang::En].get(key))
+        .map(|s| s.as_str())
+}
+
+/// Строка каталога с подставленными параметрами (см. [`tr!`](crate::tr))
+pub fn text(key: &str, args: &[(&str, &dyn Display)]) -> String {
+    let mut text = lookup(key).unwrap_or(key).to_string();
+    for (name, val) in args {
+        text = text.replace(&format!("{{{name}}}"), &val.to_string());
+    }
+    text
+}
+
+/// Локализованная строка: `tr!("prepare.create_user", name = name)`
+#[macro_export]
+macro_rules! tr {
+    ($key:literal) => {
+        $crate::i18n::text($key, &[])
+    };
+
+    ($key:literal, $($name:ident = $val:expr),+ $(,)?) => {
+        $crate::i18n::text(
+            $key,
+            &[$((stringify!($name), &$val as &dyn ::std::fmt::Display)),+],
+        )
+    };
+}
+
+/// Глобальный параметр `--lang` (значение заранее читается
+/// [`lang_from_args`])
+pub fn lang_arg() -> clap::Arg {
+    clap::Arg::new("lang")
+        .long("lang")
+        .global(true)
+        .value_name("LANG")
+        .value_parser(clap::builder::EnumValueParser::<Lang>::new())
+        .help("Interface language (default: from LC_ALL, LC_MESSAGES or LANG)")
+}
+
+/// Подставляет в справку clap строки из раздела `cli.<программа>` каталога:
+/// описание команды - `...about`, параметра - `...<параметр>` (для
+/// подкоманд путь продолжается, e.g. `cli.alfa.config.show.origin`).
+/// Строки, которых нет в каталоге, остаются как есть.
+pub fn localize_command(cmd: clap::Command) -> clap::Command {
+    let path = format!("cli.{}", cmd.get_name());
+    localize_at(&path, cmd)
+}
+
+fn localize_at(path: &str, mut cmd: clap::Command) -> clap::Command {
+    if let Some(about) = lookup(&format!("{path}.about")) {
+        cmd = cmd.about(about).long_about(None);
+    }
+
+    let ids = cmd
+        .get_arguments()
+        .map(|arg| arg.get_id().to_string())
+        .collect::<Vec<_>>();
+    for id in ids {
+        if let Some(help) = lookup(&format!("{path}.{id}")) {
+            cmd = cmd.mut_arg(id, |arg| arg.help(help).long_help(None));
+        }
+    }
+
+    let names = cmd
+        .get_subcommands()
+        .map(|sub| sub.get_name().to_string())
+        .collect::<Vec<_>>();
+    for name in names {
+        let sub_path = format!("{path}.{name}");
+        cmd = cmd.mut_subcommand(&name, |sub| localize_at(&sub_path, sub));
+    }
+
+    cmd
+}
+
+/// Текст, который может быть задан на нескольких языках:
+///
+/// ```toml
+/// description = "Description"
+/// # или
+/// description = { en = "Description", ru = "Описание" }
+/// ```
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum Localized {
+    Text(String),
+    Variants(BTreeMap<String, String>),
+}
+
+impl Localized {
+    /// Вариант для языка интерфейса, иначе английский, иначе любой
+    pub fn get(&self) -> &str {
+        match self {
+            Self::Text(text) => text,
+            Self::Variants(variants) => variants
+                .get(lang().code())
+                .or_else(|| variants.get(Lang::En.code()))
+                .or_else(|| variants.values().next())
+                .map_or("", |s| s.as_str()),
+        }
+    }
+
+    /// Все варианты текста
+    pub fn variants(&self) -> Vec<&str> {
+        match self {
+            Self::Text(text) => vec![text],
+            Self::Variants(variants) => variants.values().map(|s| s.as_str()).collect(),
+        }
+    }
+}
+
+impl Display for Localized {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.get())
+    }
+}
@@ -3,14 +3,30 @@
 use anyhow::Result;
 use colored::Colorize;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, fs, path::Path};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
 use toml;
 
-use crate::{process_msg, tui::process_msg_result_err};
+use crate::error::AlfaError;
+use crate::events::{emit, Event};
+use crate::logging;
+use crate::{process_msg, tr, tui::process_msg_result_err};
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Instruction {
     pub stage: String,
+
+    /// Где выполняется сборочный скрипт
+    #[serde(default)]
+    pub kind: StageKind,
+
+    /// Разрешить скрипту доступ к сети (по умолчанию скрипт выполняется в
+    /// песочнице без сети, см. [`crate::sandbox`])
+    #[serde(default)]
+    pub network: bool,
     pub generic_name: Option<String>,
     pub name: String, // key for `PackageList.package` map, from this we get `version`
     pub file_name: Option<String>,
@@ -19,10 +35,30 @@ pub struct Instruction {
     pub env: Option<HashMap<String, String>>,
 }
 
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum StageKind {
+    /// На хост-системе в окружении сборочного пользователя
+    #[default]
+    Host,
+
+    /// Внутри собираемой системы (`chroot` в `build_dir/lfa`) с
+    /// примонтированными `/dev`, `/dev/pts`, `/proc`, `/sys` и `/run`.
+    /// Исходный код и скрипты доступны в `/alfa/src` и `/alfa/scripts`.
+    Chroot,
+}
+
 impl Instruction {
     pub fn read<P: AsRef<Path>>(pth: P) -> Result<Self> {
-        let contents = fs::read_to_string(&pth)?;
-        let data = toml::from_str(&contents)?;
+        let path = pth.as_ref().to_path_buf();
+        if !path.exists() {
+            return Err(AlfaError::MissingInstruction { path }.into());
+        }
+        let contents = fs::read_to_string(&path)?;
+        let data = toml::from_str(&contents).map_err(|why| AlfaError::InvalidInstruction {
+            path,
+            reason: why.message().to_string(),
+        })?;
 
         Ok(data)
     }
@@ -46,6 +82,25 @@ impl Instruction {
         )
     }
 
+    /// Экспорт переменных окружения из `.config.toml` (`env_default`, `board`,
+    /// `env`) и из секции `env` самой инструкции
+    fn gen_env(&self, env: &HashMap<String, String>) -> Result<String> {
+        let mut vars = env.iter().collect::<Vec<_>>();
+        if let Some(instr_env) = &self.env {
+            vars.extend(instr_env.iter());
+        }
+        vars.sort_by(|a, b| a.0.cmp(b.0));
+
+        let mut sh = String::new();
+        for (k, v) in vars {
+            sh.push_str(&export(k, v)?);
+        }
+        if !sh.is_empty() {
+            sh.push('\n');
+        }
+        Ok(sh)
+    }
+
     /// NOTE: считаем, что скрипту передаются переменные окружения:
     /// - `ALFA_SRC_DIR` - путь до директории с исходным кодом
     fn gen_untar(&self, pkgver: &str) -> String {
@@ -75,54 +130,94 @@ impl Instruction {
     }
 
     fn gen_exit(&self) -> String {
-        format!(
-            "\ncd $ALFA_SRC_DIR\n\
+        "\ncd $ALFA_SRC_DIR\n\
             for i in *; do\n\
                 \tif [ -d $i ]; then\n\
                     \t\trm -rvf $i\n\
                 \tfi\n\
             done"
-        )
+            .to_string()
     }
 
-    fn get_sh(&self, pkgver: &str) -> String {
-        format!(
-            "{header}{untar}\n{cmd}\n{exit}",
+    fn get_sh(&self, pkgver: &str, env: &HashMap<String, String>) -> Result<String> {
+        Ok(format!(
+            "{header}{env}{untar}\n{cmd}\n{exit}",
             header = self.gen_header(pkgver),
+            env = self.gen_env(env)?,
             untar = self.gen_untar(pkgver),
             cmd = self.gen_cmd(),
             exit = self.gen_exit(),
-        )
+        ))
+    }
+
+    /// Путь до сборочного скрипта в директории `prefix`
+    pub fn script_path<P: AsRef<Path>>(&self, prefix: P) -> PathBuf {
+        prefix
+            .as_ref()
+            .join(&self.stage)
+            .join(format!("{}.sh", &self.name))
     }
 
-    pub fn gen_sh<P: AsRef<Path>>(&self, prefix: P, pkgver: &str) -> Result<()> {
-        let sh = self.get_sh(pkgver);
+    pub fn gen_sh<P: AsRef<Path>>(
+        &self,
+        prefix: P,
+        pkgver: &str,
+        env: &HashMap<String, String>,
+    ) -> Result<()> {
+        let sh = self.get_sh(pkgver, env)?;
         let pth_dir = prefix.as_ref().join(&self.stage);
 
         if !pth_dir.exists() {
-            process_msg!(
-                "Create directory '{}'",
-                &pth_dir.display().to_string().dimmed()
-            );
+            process_msg!(tr!(
+                "instruction.create_dir",
+                path = pth_dir.display().to_string().dimmed()
+            ));
             let rslt = fs::create_dir_all(&pth_dir);
-            process_msg_result_err(
-                rslt.is_ok(),
-                if let Err(err) = rslt { Some(err) } else { None },
-            );
+            process_msg_result_err(rslt.is_ok(), rslt.err());
         }
 
-        let pth = pth_dir.join(format!("{}.sh", &self.name));
-
-        process_msg!(
-            "Write script for package '{}'",
-            format!("{}-{}", &self.name, pkgver).dimmed()
-        );
-        let rslt = fs::write(pth, sh);
-        process_msg_result_err(
-            rslt.is_ok(),
-            if let Err(err) = rslt { Some(err) } else { None },
-        );
+        let pth = self.script_path(&prefix);
+
+        process_msg!(tr!(
+            "instruction.write_script",
+            package = format!("{}-{}", &self.name, pkgver).dimmed()
+        ));
+        let rslt = fs::write(&pth, sh);
+        if rslt.is_ok() {
+            logging::debug(tr!("instruction.script_written", path = pth.display()));
+            emit(&Event::ScriptGenerated {
+                package: &self.name,
+                version: pkgver,
+                path: pth.display().to_string(),
+            });
+        }
+        process_msg_result_err(rslt.is_ok(), rslt.err());
 
         Ok(())
     }
 }
+
+/// Допустимое имя переменной оболочки: латинские буквы, цифры и `_`, не
+/// начинается с цифры
+pub fn is_var_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Строка `export NAME='value'` для сценария bash. Значение заключается в
+/// одинарные кавычки (`'` внутри него записывается как `'\''`), поэтому
+/// `$`, `"`, обратные кавычки и переводы строк не интерпретируются
+/// оболочкой. Имя должно быть допустимым именем переменной.
+pub fn export(name: &str, value: &str) -> Result<String> {
+    if !is_var_name(name) {
+        return Err(AlfaError::Config(tr!("instruction.bad_env_name", name = name)).into());
+    }
+
+    Ok(format!(
+        "export {name}='{}'\n",
+        value.replace('\'', r"'\''")
+    ))
+}
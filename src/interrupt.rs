@@ -0,0 +1,42 @@
+//! SIGINT/SIGTERM handling for long-running commands
+//!
+//! Обработчик только устанавливает флаг. Команда проверяет его между шагами
+//! (сборка пакета, загрузка файла) и завершается с ошибкой
+//! [`AlfaError::Interrupted`], поэтому деструкторы успевают выполниться:
+//! размонтируются точки монтирования chroot, удаляются недокачанные файлы и
+//! восстанавливается терминал после панели `--tui`.
+
+use anyhow::Result;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::error::AlfaError;
+
+/// Устанавливается при получении SIGINT/SIGTERM
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_signal(_: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+/// Устанавливает обработчик SIGINT и SIGTERM
+pub fn install() {
+    // SAFETY: обработчик только изменяет атомарную переменную
+    unsafe {
+        let handler = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        libc::signal(libc::SIGINT, handler);
+        libc::signal(libc::SIGTERM, handler);
+    }
+}
+
+/// Был ли получен SIGINT/SIGTERM
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Возвращает ошибку, если был получен SIGINT/SIGTERM
+pub fn check() -> Result<()> {
+    match interrupted() {
+        true => Err(AlfaError::Interrupted.into()),
+        false => Ok(()),
+    }
+}
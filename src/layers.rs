@@ -0,0 +1,462 @@
+//! Layered configuration
+//!
+//! Итоговая конфигурация собирается из нескольких слоёв, каждый следующий
+//! из которых переопределяет предыдущие ключ за ключом:
+//!
+//! 1. значения по умолчанию;
+//! 2. `/etc/alfa/config.toml`;
+//! 3. `~/.config/alfa/config.toml`;
+//! 4. `.config.toml` проекта;
+//! 5. переменные окружения `ALFA_*` и параметры командной строки.
+//!
+//! Таблицы (в т.ч. `env`) сливаются, а не заменяются целиком. Исключение -
+//! таблица `board`: она описывает одну плату и заменяется целиком.
+
+use anyhow::Result;
+use std::{
+    collections::BTreeMap,
+    env,
+    path::{Path, PathBuf},
+};
+use toml::{map::Map, Value};
+
+use crate::board::Board;
+use crate::config::{Config, EnvDefault};
+use crate::error::AlfaError;
+use crate::overrides::{self, Overrides};
+use crate::schema::{self, Schema};
+use crate::tr;
+
+/// Таблица, которая не сливается с предыдущими слоями, а заменяет их
+/// значение целиком
+const REPLACED: &str = "board";
+
+/// Общесистемный файл конфигурации
+pub const SYSTEM_CONFIG: &str = "/etc/alfa/config.toml";
+
+/// Пользовательский файл конфигурации (`$XDG_CONFIG_HOME/alfa/config.toml`
+/// или `~/.config/alfa/config.toml`)
+pub fn user_config() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+
+    Some(base.join("alfa").join("config.toml"))
+}
+
+#[derive(Debug)]
+enum Source {
+    /// Таблица, которая сливается с предыдущими слоями
+    Table(Value),
+
+    /// Набор значений `key=value` (см. [`overrides::set`])
+    Set(Vec<(String, String)>),
+}
+
+/// Слой конфигурации и его происхождение (путь до файла, `environment`,
+/// `command line` и т.д.)
+#[derive(Debug)]
+pub struct Layer {
+    pub origin: String,
+    source: Source,
+}
+
+#[derive(Debug, Default)]
+pub struct Layers {
+    layers: Vec<Layer>,
+}
+
+impl Layers {
+    /// Значения по умолчанию и общие (системный и пользовательский) файлы
+    /// конфигурации
+    pub fn shared(base: &Config) -> Result<Self> {
+        let files = [Some(PathBuf::from(SYSTEM_CONFIG)), user_config()];
+        Self::with_files(base, files.into_iter().flatten())
+    }
+
+    /// Значения по умолчанию и указанные файлы (по порядку)
+    fn with_files<I: IntoIterator<Item = PathBuf>>(base: &Config, files: I) -> Result<Self> {
+        let mut layers = Self::default();
+        layers.push_table(tr!("layers.origin_default"), Value::try_from(base)?);
+        for file in files {
+            layers.push_file(file, false)?;
+        }
+
+        Ok(layers)
+    }
+
+    /// Общие слои и `.config.toml` проекта, который должен существовать.
+    /// Устаревший файл обновляется только в памяти; на диск (с резервной
+    /// копией) его записывает `alfa config`, который читает его так же.
+    pub fn load<P: AsRef<Path>>(project: P) -> Result<Self> {
+        let project = project.as_ref();
+        if !project.exists() {
+            return Err(
+                AlfaError::Config(tr!("layers.not_found", path = project.display())).into(),
+            );
+        }
+
+        let mut layers = Self::shared(&Config::defaults())?;
+        layers.push_file(project, false)?;
+
+        Ok(layers)
+    }
+
+    /// Параметры целевой системы из профиля платы
+    pub fn push_board(&mut self, board: &Board) -> Result<()> {
+        let mut table = Map::new();
+        table.insert(
+            "env_default".to_string(),
+            Value::try_from(EnvDefault::from_board(board))?,
+        );
+        table.insert("board".to_string(), Value::try_from(board)?);
+        self.push_table(
+            tr!("layers.origin_board", name = board.name),
+            Value::Table(table),
+        );
+
+        Ok(())
+    }
+
+    pub fn push_table<O: ToString>(&mut self, origin: O, value: Value) {
+        self.layers.push(Layer {
+            origin: origin.to_string(),
+            source: Source::Table(value),
+        });
+    }
+
+    /// Добавляет файл в качестве слоя, если он существует. Устаревшие файлы
+    /// обновляются до текущей версии схемы: на диске (`upgrade = true`) или
+    /// только в памяти.
+    pub fn push_file<P: AsRef<Path>>(&mut self, pth: P, upgrade: bool) -> Result<()> {
+        let pth = pth.as_ref();
+        if !pth.exists() {
+            return Ok(());
+        }
+
+        let value = schema::read_value(Schema::Config, pth, upgrade)?;
+        self.push_table(pth.display(), value);
+
+        Ok(())
+    }
+
+    /// Добавляет слои из файла ответов, переменных окружения и `--set`
+    pub fn push_overrides(&mut self, ovr: &Overrides) -> Result<()> {
+        if let Some(answers) = &ovr.answers {
+            self.push_table(answers, overrides::read_answers(answers)?);
+        }
+
+        if ovr.use_env {
+            self.push_set(tr!("layers.origin_env"), overrides::env_overrides());
+        }
+
+        if !ovr.set.is_empty() {
+            let mut items = Vec::new();
+            for item in &ovr.set {
+                let (key, raw) = item
+                    .split_once('=')
+                    .ok_or(AlfaError::Config(tr!("layers.bad_override", item = item)))?;
+                items.push((key.trim().to_string(), raw.trim().to_string()));
+            }
+            self.push_set(tr!("layers.origin_cli"), items);
+        }
+
+        Ok(())
+    }
+
+    /// Добавляет слой из пар (ключ, значение), если он не пуст
+    fn push_set(&mut self, origin: String, items: Vec<(String, String)>) {
+        if !items.is_empty() {
+            self.layers.push(Layer {
+                origin,
+                source: Source::Set(items),
+            });
+        }
+    }
+
+    /// Итоговое значение и происхождение каждого ключа
+    fn resolve(&self) -> Result<(Value, BTreeMap<String, String>)> {
+        let mut value = Value::Table(Default::default());
+        let mut origins = BTreeMap::new();
+
+        for layer in &self.layers {
+            match &layer.source {
+                Source::Table(table) => {
+                    if table.get(REPLACED).is_some() {
+                        if let Value::Table(t) = &mut value {
+                            t.remove(REPLACED);
+                        }
+                        let nested = format!("{REPLACED}.");
+                        origins.retain(|k: &String, _| !k.starts_with(&nested));
+                    }
+                    for key in leaf_keys(table, "") {
+                        origins.insert(key, layer.origin.clone());
+                    }
+                    overrides::merge(&mut value, table.clone());
+                }
+                Source::Set(items) => {
+                    for (key, raw) in items {
+                        overrides::set(&mut value, key, raw)?;
+                        // значение заменяется целиком, поэтому происхождение
+                        // вложенных ключей больше не актуально
+                        let nested = format!("{key}.");
+                        origins.retain(|k, _| k != key && !k.starts_with(&nested));
+                        if !raw.is_empty() {
+                            origins.insert(key.clone(), layer.origin.clone());
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok((value, origins))
+    }
+
+    pub fn value(&self) -> Result<Value> {
+        Ok(self.resolve()?.0)
+    }
+
+    /// Итоговая конфигурация. Ключи, которые `Config` не использует (e.g.
+    /// опечатка в `--set system.nmae=...`), считаются ошибкой.
+    pub fn config(&self) -> Result<Config> {
+        let (value, origins) = self.resolve()?;
+        let conf: Config = value
+            .clone()
+            .try_into()
+            .map_err(|why| AlfaError::Config(tr!("layers.invalid", why = why)))?;
+
+        let known = leaf_keys(&Value::try_from(&conf)?, "");
+        if let Some(key) = leaf_keys(&value, "")
+            .into_iter()
+            .find(|key| !known.contains(key))
+        {
+            return Err(AlfaError::Config(tr!(
+                "layers.unknown_key",
+                key = key,
+                origin = origin_of(&origins, &key)
+            ))
+            .into());
+        }
+
+        Ok(conf)
+    }
+
+    /// Возвращает тройки (ключ, значение, происхождение) для каждого
+    /// итогового значения
+    pub fn origins(&self) -> Result<Vec<(String, Value, String)>> {
+        let (value, origins) = self.resolve()?;
+        let mut rslt = Vec::new();
+
+        for key in leaf_keys(&value, "") {
+            let origin = origin_of(&origins, &key);
+            if let Some(val) = lookup(&value, &key) {
+                rslt.push((key, val.clone(), origin));
+            }
+        }
+
+        Ok(rslt)
+    }
+}
+
+/// Полные ключи всех листовых значений таблицы (e.g. `system.name`)
+fn leaf_keys(value: &Value, prefix: &str) -> Vec<String> {
+    match value {
+        Value::Table(table) => {
+            let mut keys = Vec::new();
+            for (k, v) in table {
+                let key = if prefix.is_empty() {
+                    k.clone()
+                } else {
+                    format!("{prefix}.{k}")
+                };
+                match v {
+                    Value::Table(_) => keys.extend(leaf_keys(v, &key)),
+                    _ => keys.push(key),
+                }
+            }
+            keys
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Происхождение ключа, либо ближайшей родительской таблицы, заданной
+/// целиком
+fn origin_of(origins: &BTreeMap<String, String>, key: &str) -> String {
+    let mut part = key;
+    loop {
+        if let Some(origin) = origins.get(part) {
+            return origin.clone();
+        }
+        match part.rsplit_once('.') {
+            Some((parent, _)) => part = parent,
+            None => return String::new(),
+        }
+    }
+}
+
+fn lookup<'a>(value: &'a Value, key: &str) -> Option<&'a Value> {
+    key.split('.').try_fold(value, |node, part| node.get(part))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, process};
+
+    /// Временная директория с файлами конфигурации
+    fn write_files(test: &str, files: &[(&str, &str)]) -> Vec<PathBuf> {
+        let dir = env::temp_dir().join(format!("alfa-layers-{}-{test}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        files
+            .iter()
+            .map(|(name, contents)| {
+                let pth = dir.join(name);
+                fs::write(&pth, contents).unwrap();
+                pth
+            })
+            .collect()
+    }
+
+    fn origin(layers: &Layers, key: &str) -> String {
+        let origins = layers.origins().unwrap();
+        let (_, _, origin) = origins.iter().find(|(k, _, _)| k == key).unwrap();
+        origin.clone()
+    }
+
+    fn vars(items: &[(&str, &str)]) -> Vec<(String, String)> {
+        items
+            .iter()
+            .map(|(key, val)| (key.to_string(), val.to_string()))
+            .collect()
+    }
+
+    /// Слои в порядке: значения по умолчанию, `/etc`, `~/.config`, проект,
+    /// `ALFA_*`, `--set`
+    fn stack(test: &str) -> (Layers, Vec<PathBuf>) {
+        let files = write_files(
+            test,
+            &[
+                (
+                    "etc.toml",
+                    "[system]\nname = \"etc\"\nauthor = \"etc\"\n[env]\nA = \"etc\"\nB = \"etc\"\n",
+                ),
+                (
+                    "user.toml",
+                    "[system]\nname = \"user\"\n[env]\nB = \"user\"\nC = \"user\"\n",
+                ),
+                (
+                    "project.toml",
+                    "[system]\nname = \"project\"\nversion = \"2.0\"\n[env]\nC = \"project\"\n",
+                ),
+            ],
+        );
+
+        let mut layers =
+            Layers::with_files(&Config::defaults(), files[..2].iter().cloned()).unwrap();
+        layers.push_file(&files[2], false).unwrap();
+        layers.push_set(
+            tr!("layers.origin_env"),
+            overrides::env_overrides_from(vars(&[
+                ("ALFA_SYSTEM_NAME", "env"),
+                ("ALFA_ENV_D", "env"),
+            ])),
+        );
+        layers
+            .push_overrides(&Overrides {
+                answers: None,
+                set: vec![
+                    "env.D=cli".to_string(),
+                    "env_default.lfa_arch = armv8.2-a".to_string(),
+                ],
+                use_env: false,
+            })
+            .unwrap();
+
+        (layers, files)
+    }
+
+    #[test]
+    fn merge_order() {
+        let (layers, files) = stack("merge");
+        let conf = layers.config().unwrap();
+        fs::remove_dir_all(files[0].parent().unwrap()).unwrap();
+
+        assert_eq!(conf.system.name, "env");
+        assert_eq!(conf.system.author, "etc");
+        assert_eq!(conf.system.version, "2.0");
+        assert_eq!(conf.env["A"], "etc");
+        assert_eq!(conf.env["B"], "user");
+        assert_eq!(conf.env["C"], "project");
+        assert_eq!(conf.env["D"], "cli");
+        assert_eq!(conf.env_default.lfa_arch, "armv8.2-a");
+        assert_eq!(
+            conf.env_default.lfa_tgt,
+            Config::defaults().env_default.lfa_tgt
+        );
+    }
+
+    #[test]
+    fn origins() {
+        let (layers, files) = stack("origins");
+        let path = |i: usize| files[i].display().to_string();
+        fs::remove_dir_all(files[0].parent().unwrap()).unwrap();
+
+        assert_eq!(origin(&layers, "system.author"), path(0));
+        assert_eq!(origin(&layers, "env.B"), path(1));
+        assert_eq!(origin(&layers, "system.version"), path(2));
+        assert_eq!(origin(&layers, "env.C"), path(2));
+        assert_eq!(origin(&layers, "system.name"), tr!("layers.origin_env"));
+        assert_eq!(origin(&layers, "env.D"), tr!("layers.origin_cli"));
+        assert_eq!(
+            origin(&layers, "env_default.lfa_arch"),
+            tr!("layers.origin_cli")
+        );
+        assert_eq!(
+            origin(&layers, "env_default.bits"),
+            tr!("layers.origin_default")
+        );
+    }
+
+    #[test]
+    fn board_is_replaced() {
+        let mut layers = Layers::default();
+        let table = |s: &str| toml::from_str::<Value>(s).unwrap();
+        layers.push_table("first", table("[board]\nname = \"a\"\nsoc = \"rk3328\"\n"));
+        layers.push_table("second", table("[board]\nname = \"b\"\n"));
+
+        let value = layers.value().unwrap();
+        assert_eq!(value["board"]["name"].as_str(), Some("b"));
+        assert!(value["board"].get("soc").is_none());
+        assert_eq!(origin(&layers, "board.name"), "second");
+    }
+
+    #[test]
+    fn set_errors() {
+        let mut layers = Layers::with_files(&Config::defaults(), []).unwrap();
+        let bad = Overrides {
+            answers: None,
+            set: vec!["system.name".to_string()],
+            use_env: false,
+        };
+        assert!(layers.push_overrides(&bad).is_err());
+
+        layers
+            .push_overrides(&Overrides {
+                answers: None,
+                set: vec!["system.nmae=ALFA".to_string()],
+                use_env: false,
+            })
+            .unwrap();
+        let why = layers.config().unwrap_err().to_string();
+        assert_eq!(
+            why,
+            tr!(
+                "layers.unknown_key",
+                key = "system.nmae",
+                origin = tr!("layers.origin_cli")
+            )
+        );
+    }
+}
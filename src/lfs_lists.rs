@@ -0,0 +1,338 @@
+//! LFS-style `wget-list` and `md5sums` files (`alfa packages`)
+//!
+//! `wget-list` содержит адреса архивов (по одному на строку), `md5sums` -
+//! строки вида `<md5>  <имя файла>`, как в руководстве LFS. Экспорт
+//! формирует эти файлы из `packages.toml`, импорт - заготовку
+//! `packages.toml` из них: имя и версия пакета определяются по имени архива
+//! (см. [`infer`]), а всё, что определить не удалось, возвращается в виде
+//! списка проблем ([`Problem`]).
+
+use anyhow::Result;
+use indexmap::IndexMap;
+use regex::Regex;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::{self, Display},
+};
+
+use crate::build_meta::{Package, PackageList};
+use crate::downloader::file_name;
+use crate::tr;
+
+/// Расширения архивов исходного кода
+const ARCHIVE_EXTENSIONS: [&str; 12] = [
+    ".tar.gz",
+    ".tar.xz",
+    ".tar.bz2",
+    ".tar.zst",
+    ".tar.lz",
+    ".tar.lzma",
+    ".tgz",
+    ".tbz2",
+    ".txz",
+    ".tar",
+    ".zip",
+    ".tar.Z",
+];
+
+/// Суффиксы имён архивов, не относящиеся к версии (e.g. `tcl8.6.14-src`)
+const NAME_SUFFIXES: [&str; 3] = ["-src", "-source", ".orig"];
+
+/// Пакеты в алфавитном порядке (для `wget-list`, `md5sums` и заготовки
+/// `packages.toml`)
+fn sorted(packages: &PackageList) -> BTreeMap<&str, &Package> {
+    packages
+        .package
+        .iter()
+        .map(|(name, pkg)| (name.as_str(), pkg))
+        .collect()
+}
+
+/// Содержимое `wget-list`: адреса архивов всех пакетов
+pub fn wget_list(packages: &PackageList) -> String {
+    sorted(packages)
+        .values()
+        .map(|pkg| format!("{}\n", pkg.download))
+        .collect()
+}
+
+/// Содержимое `md5sums` и имена пакетов без контрольной суммы (они в файл
+/// не попадают)
+pub fn md5sums(packages: &PackageList) -> (String, Vec<String>) {
+    let mut contents = String::new();
+    let mut missing = Vec::new();
+    for (name, pkg) in sorted(packages) {
+        match pkg.md5.is_empty() {
+            true => missing.push(name.to_string()),
+            false => contents.push_str(&format!("{}  {}\n", pkg.md5, file_name(&pkg.download))),
+        }
+    }
+
+    (contents, missing)
+}
+
+/// Имя и версия пакета по имени архива: `gmp-6.3.0.tar.xz` -> (`gmp`,
+/// `6.3.0`), `wireless_tools.29.tar.gz` -> (`wireless_tools`, `29`),
+/// `tcl8.6.14-src.tar.gz` -> (`tcl`, `8.6.14`). `None`, если файл не похож
+/// на архив или версию определить не удалось.
+pub fn infer(file: &str) -> Option<(String, String)> {
+    let stem = archive_stem(file)?;
+    let stem = NAME_SUFFIXES
+        .iter()
+        .find_map(|sfx| stem.strip_suffix(sfx))
+        .unwrap_or(stem);
+
+    // разделители в порядке убывания надёжности: `name-1.2`, `name1.2`,
+    // `name.1.2`
+    let patterns = [
+        r"^(.+?)[-_]v?([0-9][0-9A-Za-z.+~_-]*)$",
+        r"^([A-Za-z][A-Za-z+]*)([0-9][0-9.]*)$",
+        r"^(.+?)\.([0-9][0-9A-Za-z.+~_-]*)$",
+    ];
+    patterns.iter().find_map(|re| {
+        let c = Regex::new(re).ok()?.captures(stem)?;
+        Some((c[1].to_string(), c[2].to_string()))
+    })
+}
+
+/// Имя архива без расширения
+fn archive_stem(file: &str) -> Option<&str> {
+    ARCHIVE_EXTENSIONS
+        .iter()
+        .find_map(|ext| file.strip_suffix(ext))
+        .filter(|stem| !stem.is_empty())
+}
+
+/// То, что не удалось перенести при импорте
+#[derive(Debug, Clone, PartialEq)]
+pub enum Problem {
+    /// Файл не является архивом исходного кода (e.g. патч)
+    NotArchive { file: String },
+
+    /// Версию не удалось определить по имени архива
+    NoVersion { file: String },
+
+    /// Имя пакета совпадает с именем уже импортированного пакета
+    Duplicate { name: String, file: String },
+
+    /// Для архива нет контрольной суммы в `md5sums`
+    NoChecksum { file: String },
+
+    /// Контрольная сумма из `md5sums` не относится ни к одному архиву
+    UnusedChecksum { file: String },
+
+    /// Строку `md5sums` не удалось разобрать
+    BadLine { line: usize },
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            Self::NotArchive { file } => tr!("lfs_lists.not_archive", file = file),
+            Self::NoVersion { file } => tr!("lfs_lists.no_version", file = file),
+            Self::Duplicate { name, file } => {
+                tr!("lfs_lists.duplicate", name = name, file = file)
+            }
+            Self::NoChecksum { file } => tr!("lfs_lists.no_checksum", file = file),
+            Self::UnusedChecksum { file } => tr!("lfs_lists.unused_checksum", file = file),
+            Self::BadLine { line } => tr!("lfs_lists.bad_line", line = line),
+        };
+        write!(f, "{text}")
+    }
+}
+
+/// Разбирает `md5sums`: имя файла -> контрольная сумма. Пустые строки и
+/// комментарии (`#`) пропускаются.
+pub fn parse_md5sums(contents: &str) -> (HashMap<String, String>, Vec<Problem>) {
+    let mut sums = HashMap::new();
+    let mut problems = Vec::new();
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        // `<md5>  <файл>` или `<md5> *<файл>` (вывод `md5sum -b`)
+        match line.split_once(char::is_whitespace) {
+            Some((md5, file)) if md5.len() == 32 && md5.chars().all(|c| c.is_ascii_hexdigit()) => {
+                let file = file.trim_start();
+                let file = file.strip_prefix('*').unwrap_or(file);
+                sums.insert(file.to_string(), md5.to_lowercase());
+            }
+            _ => problems.push(Problem::BadLine { line: i + 1 }),
+        }
+    }
+
+    (sums, problems)
+}
+
+/// Результат импорта `wget-list` и `md5sums`
+#[derive(Debug)]
+pub struct Import {
+    pub packages: PackageList,
+    pub problems: Vec<Problem>,
+}
+
+impl Import {
+    /// Заготовка `packages.toml` по содержимому `wget-list` и (если есть)
+    /// `md5sums`. Пакеты, версию которых определить не удалось, попадают в
+    /// заготовку с пустой версией; пакеты без контрольной суммы - с пустой
+    /// `md5`.
+    pub fn new(wget_list: &str, md5sums: Option<&str>) -> Self {
+        let (mut sums, mut problems) = match md5sums {
+            Some(contents) => parse_md5sums(contents),
+            None => (HashMap::new(), Vec::new()),
+        };
+        let mut package = IndexMap::new();
+
+        let urls = wget_list
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'));
+        for url in urls {
+            let file = file_name(url).to_string();
+
+            let (name, version) = match (archive_stem(&file), infer(&file)) {
+                (None, _) => {
+                    // контрольная сумма патча не считается лишней
+                    sums.remove(&file);
+                    problems.push(Problem::NotArchive { file });
+                    continue;
+                }
+                (Some(stem), None) => {
+                    problems.push(Problem::NoVersion { file: file.clone() });
+                    (stem.to_string(), String::new())
+                }
+                (_, Some(inferred)) => inferred,
+            };
+            // контрольная сумма дубликата остаётся в `sums` и попадает в
+            // список неиспользованных
+            if package.contains_key(&name) {
+                problems.push(Problem::Duplicate { name, file });
+                continue;
+            }
+            let md5 = sums.remove(&file);
+            if md5.is_none() && md5sums.is_some() {
+                problems.push(Problem::NoChecksum { file });
+            }
+
+            package.insert(
+                name,
+                Package {
+                    version,
+                    description: None,
+                    home_page: None,
+                    license: None,
+                    download: url.to_string(),
+                    md5: md5.unwrap_or_default(),
+                    size_source: None,
+                    size_build: None,
+                    notes: None,
+                    check: None,
+                },
+            );
+        }
+
+        let mut unused = sums.into_keys().collect::<Vec<_>>();
+        unused.sort();
+        problems.extend(
+            unused
+                .into_iter()
+                .map(|file| Problem::UnusedChecksum { file }),
+        );
+
+        package.sort_unstable_keys();
+
+        Self {
+            packages: PackageList { package },
+            problems,
+        }
+    }
+
+    /// Заготовка `packages.toml` (пакеты в алфавитном порядке)
+    pub fn to_toml(&self) -> Result<String> {
+        Ok(toml::to_string(&self.packages)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pair(name: &str, version: &str) -> Option<(String, String)> {
+        Some((name.to_string(), version.to_string()))
+    }
+
+    #[test]
+    fn infer_name_and_version() {
+        assert_eq!(infer("gmp-6.3.0.tar.xz"), pair("gmp", "6.3.0"));
+        assert_eq!(infer("tcl8.6.14-src.tar.gz"), pair("tcl", "8.6.14"));
+        assert_eq!(
+            infer("wireless_tools.29.tar.gz"),
+            pair("wireless_tools", "29")
+        );
+        assert_eq!(
+            infer("iana-etc-20241122.tar.gz"),
+            pair("iana-etc", "20241122")
+        );
+        assert_eq!(infer("bzip2-1.0.8-install_docs-1.patch"), None);
+        assert_eq!(infer("rkbin.tar.xz"), None);
+    }
+
+    #[test]
+    fn parse_md5sums_lines() {
+        let (sums, problems) = parse_md5sums(
+            "# LFS md5sums\n\
+            \n\
+            6DF2C48B6B6B4EEB1C07E5DEAA9E0A1E  gmp-6.3.0.tar.xz\n\
+            1bd2a9e1c2c9e1b1d1a1e1c1b1d1a1e1 *tcl8.6.14-src.tar.gz\n\
+            not-a-checksum  foo.tar.gz\n",
+        );
+
+        assert_eq!(
+            sums.get("gmp-6.3.0.tar.xz").map(String::as_str),
+            Some("6df2c48b6b6b4eeb1c07e5deaa9e0a1e")
+        );
+        assert_eq!(
+            sums.get("tcl8.6.14-src.tar.gz").map(String::as_str),
+            Some("1bd2a9e1c2c9e1b1d1a1e1c1b1d1a1e1")
+        );
+        assert_eq!(sums.len(), 2);
+        assert_eq!(problems, [Problem::BadLine { line: 5 }]);
+    }
+
+    #[test]
+    fn import_reports_duplicate_checksum_as_unused() {
+        let import = Import::new(
+            "https://example.org/gmp-6.3.0.tar.xz\n\
+            https://example.org/gmp-6.2.1.tar.xz\n\
+            https://example.org/bzip2-1.0.8-install_docs-1.patch\n",
+            Some(
+                "00000000000000000000000000000001  gmp-6.3.0.tar.xz\n\
+                00000000000000000000000000000002  gmp-6.2.1.tar.xz\n\
+                00000000000000000000000000000003  bzip2-1.0.8-install_docs-1.patch\n",
+            ),
+        );
+
+        assert_eq!(import.packages.package["gmp"].version, "6.3.0");
+        assert_eq!(
+            import.packages.package["gmp"].md5,
+            "00000000000000000000000000000001"
+        );
+        assert_eq!(
+            import.problems,
+            [
+                Problem::Duplicate {
+                    name: "gmp".to_string(),
+                    file: "gmp-6.2.1.tar.xz".to_string(),
+                },
+                Problem::NotArchive {
+                    file: "bzip2-1.0.8-install_docs-1.patch".to_string(),
+                },
+                Problem::UnusedChecksum {
+                    file: "gmp-6.2.1.tar.xz".to_string(),
+                },
+            ]
+        );
+    }
+}
@@ -1,9 +1,35 @@
 //! # ALFA - Automated Linux for ARM
 
+pub mod board;
+pub mod build;
 pub mod build_meta;
 pub mod config;
+pub mod dashboard;
 pub mod downloader;
+pub mod error;
+pub mod events;
+pub mod host;
+pub mod i18n;
 pub mod instruction;
+pub mod interrupt;
+pub mod layers;
+pub mod lfs_lists;
+pub mod logging;
+pub mod menuconfig;
+pub mod mounts;
+pub mod overrides;
+pub mod pkginfo;
 pub mod prepare;
 pub mod profile;
+pub mod qemu;
+pub mod report;
+pub mod rootless;
+pub mod sandbox;
+pub mod schema;
+pub mod shell_env;
+pub mod space;
+pub mod spdx;
+pub mod timing;
 pub mod tui;
+pub mod upstream;
+pub mod validate;
@@ -0,0 +1,216 @@
+//! Leveled logging to the console and `build_dir/alfa.log`
+//!
+//! Действия ALFA (создание директорий и пользователя, загрузка файлов,
+//! создание сборочных скриптов, сборка пакетов) записываются с отметками
+//! времени в журнал `build_dir/alfa.log`, который дополняется при каждом
+//! запуске `alfa prepare` и `alfa build`. Записи, сделанные до открытия
+//! журнала (пока сборочная директория не создана), хранятся в памяти и
+//! записываются в журнал при его открытии.
+//!
+//! Подробность вывода в консоль задаётся параметрами `-q`, `-v` и `-vv`
+//! (см. [`Verbosity`]). В журнал записываются все сообщения уровня `debug`
+//! и выше, сообщения уровня `trace` - только при `-vv`.
+
+use anyhow::Result;
+use colored::Colorize;
+use std::{
+    fmt::{self, Display},
+    fs::{File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, OnceLock,
+    },
+};
+
+use crate::events::is_human;
+use crate::timing::{format_time, unix_time};
+
+/// Имя файла журнала в сборочной директории
+pub const LOG_FILE: &str = "alfa.log";
+
+static VERBOSITY: OnceLock<Verbosity> = OnceLock::new();
+
+/// В консоли выведено начало операции без перевода строки (см.
+/// [`crate::process_msg!`])
+pub static LINE_OPEN: AtomicBool = AtomicBool::new(false);
+
+static SINK: Mutex<Sink> = Mutex::new(Sink {
+    file: None,
+    pending: Vec::new(),
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::Error => "ERROR",
+            Self::Warn => "WARN",
+            Self::Info => "INFO",
+            Self::Debug => "DEBUG",
+            Self::Trace => "TRACE",
+        };
+        f.pad(s)
+    }
+}
+
+/// Подробность вывода в консоль
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    /// Только предупреждения и ошибки (`-q`)
+    Quiet,
+
+    #[default]
+    Normal,
+
+    /// Подробности: выполняемые команды, адреса загрузки, пути (`-v`)
+    Verbose,
+
+    /// Все сообщения (`-vv`)
+    Trace,
+}
+
+impl Verbosity {
+    /// Подробность по числу параметров `-v` и параметру `-q`
+    pub fn from_flags(verbose: u8, quiet: bool) -> Self {
+        match (quiet, verbose) {
+            (true, _) => Self::Quiet,
+            (_, 0) => Self::Normal,
+            (_, 1) => Self::Verbose,
+            _ => Self::Trace,
+        }
+    }
+
+    /// Самый подробный уровень сообщений, выводимых в консоль
+    fn max_level(&self) -> Level {
+        match self {
+            Self::Quiet => Level::Warn,
+            Self::Normal => Level::Info,
+            Self::Verbose => Level::Debug,
+            Self::Trace => Level::Trace,
+        }
+    }
+}
+
+pub fn set_verbosity(verbosity: Verbosity) {
+    let _ = VERBOSITY.set(verbosity);
+}
+
+pub fn verbosity() -> Verbosity {
+    VERBOSITY.get().copied().unwrap_or_default()
+}
+
+/// Выводятся ли в консоль сообщения уровня `level`. Вне режима вывода для
+/// человека (JSON, полноэкранная панель) консоль занята событиями.
+pub fn console(level: Level) -> bool {
+    is_human() && level <= verbosity().max_level()
+}
+
+struct Sink {
+    file: Option<File>,
+
+    /// Записи, сделанные до открытия журнала
+    pending: Vec<String>,
+}
+
+/// Путь до журнала в сборочной директории `build_dir`
+pub fn path<P: AsRef<Path>>(build_dir: P) -> PathBuf {
+    build_dir.as_ref().join(LOG_FILE)
+}
+
+/// Открывает журнал `build_dir/alfa.log` (файл дополняется) и записывает в
+/// него отложенные записи
+pub fn open<P: AsRef<Path>>(build_dir: P) -> Result<()> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path(build_dir))?;
+
+    let args = std::env::args().collect::<Vec<_>>().join(" ");
+    writeln!(file, "{} ==== {args} ====", format_time(unix_time()))?;
+
+    let mut sink = SINK.lock().unwrap_or_else(|e| e.into_inner());
+    for line in sink.pending.drain(..) {
+        writeln!(file, "{line}")?;
+    }
+    sink.file = Some(file);
+
+    Ok(())
+}
+
+/// Записывает сообщение в журнал. Сообщения уровней `debug` и `trace`
+/// также выводятся в консоль в зависимости от [`Verbosity`]; сообщения
+/// остальных уровней выводятся в консоль вызывающим кодом (см.
+/// [`crate::msg!`], [`crate::process_msg!`]).
+pub fn log<M: Display>(level: Level, message: M) {
+    let message = strip_ansi(&message.to_string());
+
+    if level >= Level::Debug && console(level) {
+        if LINE_OPEN.swap(false, Ordering::SeqCst) {
+            println!();
+        }
+        println!(
+            "{}",
+            format!("[{}] {message}", level.to_string().to_lowercase()).dimmed()
+        );
+    }
+
+    if level == Level::Trace && verbosity() < Verbosity::Trace {
+        return;
+    }
+    let line = format!("{} {level:<5} {message}", format_time(unix_time()));
+    let mut sink = SINK.lock().unwrap_or_else(|e| e.into_inner());
+    match sink.file.as_mut() {
+        Some(file) => {
+            let _ = writeln!(file, "{line}");
+        }
+        None => sink.pending.push(line),
+    }
+}
+
+pub fn error<M: Display>(message: M) {
+    log(Level::Error, message)
+}
+
+pub fn warn<M: Display>(message: M) {
+    log(Level::Warn, message)
+}
+
+pub fn info<M: Display>(message: M) {
+    log(Level::Info, message)
+}
+
+pub fn debug<M: Display>(message: M) {
+    log(Level::Debug, message)
+}
+
+pub fn trace<M: Display>(message: M) {
+    log(Level::Trace, message)
+}
+
+/// Удаляет из строки управляющие последовательности цвета (`ESC [ ... m`)
+fn strip_ansi(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
@@ -0,0 +1,662 @@
+//! Menu-driven configuration editor (`alfa config edit`)
+//!
+//! Полноэкранный редактор `.config.toml` в духе `make menuconfig`. Параметры
+//! сгруппированы по разделам (`system`, `env_default`, `env`); значения
+//! редактируются на месте, разрядность и известные значения архитектуры,
+//! ABI и FPU выбираются из списков. Параметры целевой системы проверяются
+//! при каждом изменении (см. `alfa::validate`), конфигурация с ошибками не
+//! сохраняется.
+//!
+//! Клавиши: `↑`/`↓` - выбор параметра, `Enter` - изменить, `a` - добавить
+//! переменную окружения, `d` - удалить её, `s` - сохранить, `q`/`Esc` -
+//! выход.
+
+use anyhow::{Error, Result};
+use ratatui::{
+    crossterm::event::{self, Event as Input, KeyCode, KeyEvent, KeyEventKind},
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, Clear, List, ListItem, ListState, Paragraph},
+    DefaultTerminal, Frame,
+};
+use std::{
+    io::{stdout, IsTerminal},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use crate::config::{Bits, Config};
+use crate::instruction::is_var_name;
+use crate::tr;
+use crate::tui::is_interactive;
+use crate::validate::{check_env_default, ARCH_32, ARCH_64, FLOAT_ABI, FPU};
+
+/// Пункт списка, означающий отсутствие значения
+const NONE: &str = "(none)";
+
+/// Пункт списка для ввода значения, которого нет в списке
+const CUSTOM: &str = "(custom...)";
+
+/// Ширина колонки с названиями параметров
+const LABEL_WIDTH: usize = 16;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Field {
+    Name,
+    Version,
+    Author,
+    Bits,
+    Host,
+    Target,
+    Arch,
+    Float,
+    Fpu,
+    Env(String),
+
+    /// Добавление новой переменной окружения
+    AddEnv,
+}
+
+#[derive(Debug)]
+enum Row {
+    Header(String),
+    Field(Field),
+}
+
+impl Field {
+    fn label(&self) -> String {
+        match self {
+            Self::Name => "name",
+            Self::Version => "version",
+            Self::Author => "author",
+            Self::Bits => "bits",
+            Self::Host => "lfa_host",
+            Self::Target => "lfa_tgt",
+            Self::Arch => "lfa_arch",
+            Self::Float => "lfa_float",
+            Self::Fpu => "lfa_fpu",
+            Self::Env(key) => key,
+            Self::AddEnv => return tr!("menuconfig.add_var"),
+        }
+        .to_string()
+    }
+
+    fn value(&self, conf: &Config) -> String {
+        let env = &conf.env_default;
+        match self {
+            Self::Name => conf.system.name.clone(),
+            Self::Version => conf.system.version.clone(),
+            Self::Author => conf.system.author.clone(),
+            Self::Bits => env.bits.to_string(),
+            Self::Host => env.lfa_host.clone(),
+            Self::Target => env.lfa_tgt.clone(),
+            Self::Arch => env.lfa_arch.clone(),
+            Self::Float => env.lfa_float.clone().unwrap_or_default(),
+            Self::Fpu => env.lfa_fpu.clone().unwrap_or_default(),
+            Self::Env(key) => conf.env.get(key).cloned().unwrap_or_default(),
+            Self::AddEnv => String::new(),
+        }
+    }
+
+    /// Значения для выбора из списка
+    fn options(&self, conf: &Config) -> Option<Vec<String>> {
+        let list = |items: &[&str]| items.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        match self {
+            Self::Bits => Some(list(&["arm", "arm64"])),
+            Self::Arch => {
+                let mut opts = match conf.env_default.bits {
+                    Bits::Arm32 => list(ARCH_32),
+                    Bits::Arm64 => list(ARCH_64),
+                };
+                opts.push(CUSTOM.to_string());
+                Some(opts)
+            }
+            Self::Float => Some(
+                [NONE]
+                    .iter()
+                    .chain(FLOAT_ABI)
+                    .map(|s| s.to_string())
+                    .collect(),
+            ),
+            Self::Fpu => Some(
+                [NONE]
+                    .iter()
+                    .chain(FPU.iter().map(|(name, _)| name))
+                    .map(|s| s.to_string())
+                    .collect(),
+            ),
+            _ => None,
+        }
+    }
+
+    /// Устанавливает значение; возвращает описание ошибки, если значение
+    /// недопустимо
+    fn set(&self, conf: &mut Config, value: String) -> Result<(), String> {
+        let required = |value: String| match value.trim() {
+            "" => Err(tr!("menuconfig.empty", field = self.label())),
+            value => Ok(value.to_string()),
+        };
+        let optional = |value: String| match value.trim() {
+            "" | NONE => None,
+            value => Some(value.to_string()),
+        };
+        let env = &mut conf.env_default;
+
+        match self {
+            Self::Name => conf.system.name = required(value)?,
+            Self::Version => conf.system.version = required(value)?,
+            Self::Author => conf.system.author = value.trim().to_string(),
+            Self::Bits => {
+                let bits = Bits::from_str(value.trim())?;
+                // при смене разрядности прежние значения не подходят (как в
+                // мастере настройки)
+                if bits != env.bits {
+                    let (tgt, arch, float, fpu) = match bits {
+                        Bits::Arm32 => (
+                            "arm-linux-musleabihf",
+                            "armv7-a",
+                            Some("hard".to_string()),
+                            Some("neon-vfpv4".to_string()),
+                        ),
+                        Bits::Arm64 => ("aarch64-linux-musl", "armv8.1-a", None, None),
+                    };
+                    env.lfa_tgt = tgt.to_string();
+                    env.lfa_arch = arch.to_string();
+                    env.lfa_float = float;
+                    env.lfa_fpu = fpu;
+                    env.bits = bits;
+                }
+            }
+            Self::Host => env.lfa_host = required(value)?,
+            Self::Target => env.lfa_tgt = required(value)?,
+            Self::Arch => env.lfa_arch = required(value)?,
+            Self::Float => env.lfa_float = optional(value),
+            Self::Fpu => env.lfa_fpu = optional(value),
+            Self::Env(key) => {
+                conf.env.insert(key.clone(), value);
+            }
+            Self::AddEnv => {
+                let key = value.trim();
+                if !is_var_name(key) {
+                    return Err(tr!("menuconfig.bad_var_name", name = key));
+                }
+                if conf.env.contains_key(key) {
+                    return Err(tr!("menuconfig.var_exists", name = key));
+                }
+                conf.env.insert(key.to_string(), String::new());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Строка ввода
+#[derive(Debug, Default)]
+struct TextInput {
+    text: String,
+
+    /// Позиция курсора (в символах)
+    cursor: usize,
+}
+
+impl TextInput {
+    fn new(text: String) -> Self {
+        Self {
+            cursor: text.chars().count(),
+            text,
+        }
+    }
+
+    fn byte_pos(&self) -> usize {
+        self.text
+            .char_indices()
+            .nth(self.cursor)
+            .map_or(self.text.len(), |(i, _)| i)
+    }
+
+    fn handle(&mut self, code: KeyCode) {
+        match code {
+            KeyCode::Char(c) => {
+                let pos = self.byte_pos();
+                self.text.insert(pos, c);
+                self.cursor += 1;
+            }
+            KeyCode::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                let pos = self.byte_pos();
+                self.text.remove(pos);
+            }
+            KeyCode::Delete if self.cursor < self.text.chars().count() => {
+                let pos = self.byte_pos();
+                self.text.remove(pos);
+            }
+            KeyCode::Left => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Right => self.cursor = (self.cursor + 1).min(self.text.chars().count()),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = self.text.chars().count(),
+            _ => {}
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Mode {
+    Browse,
+    Edit {
+        field: Field,
+        input: TextInput,
+    },
+    Pick {
+        field: Field,
+        options: Vec<String>,
+        list: ListState,
+    },
+    ConfirmQuit,
+}
+
+struct Editor {
+    conf: Config,
+    path: PathBuf,
+    rows: Vec<Row>,
+    list: ListState,
+    mode: Mode,
+    modified: bool,
+
+    /// Результат последнего действия (текст и признак ошибки)
+    status: Option<(String, bool)>,
+}
+
+/// Открывает редактор конфигурации `conf`, которая сохраняется в `path`
+pub fn edit<P: AsRef<Path>>(conf: Config, path: P) -> Result<()> {
+    if !is_interactive() || !stdout().is_terminal() {
+        return Err(Error::msg(tr!("menuconfig.not_interactive")));
+    }
+
+    let mut editor = Editor {
+        conf,
+        path: path.as_ref().to_path_buf(),
+        rows: Vec::new(),
+        list: ListState::default(),
+        mode: Mode::Browse,
+        modified: false,
+        status: None,
+    };
+    editor.rebuild_rows(None);
+
+    let mut terminal = ratatui::try_init()?;
+    let rslt = editor.run(&mut terminal);
+    ratatui::restore();
+
+    rslt
+}
+
+impl Editor {
+    /// Перестраивает список параметров (после изменения `env`) и выбирает
+    /// параметр `select` либо первый параметр
+    fn rebuild_rows(&mut self, select: Option<Field>) {
+        use Field::*;
+
+        let mut rows = vec![Row::Header(tr!("menuconfig.system"))];
+        rows.extend([Name, Version, Author].map(Row::Field));
+
+        let target = match &self.conf.board {
+            Some(board) => tr!("menuconfig.target_board", board = board.name),
+            None => tr!("menuconfig.target"),
+        };
+        rows.push(Row::Header(target));
+        rows.extend([Bits, Host, Target, Arch, Float, Fpu].map(Row::Field));
+
+        rows.push(Row::Header(tr!("menuconfig.env")));
+        let mut keys = self.conf.env.keys().cloned().collect::<Vec<_>>();
+        keys.sort();
+        rows.extend(keys.into_iter().map(|key| Row::Field(Env(key))));
+        rows.push(Row::Field(AddEnv));
+
+        self.rows = rows;
+        let idx = select
+            .and_then(|field| {
+                self.rows
+                    .iter()
+                    .position(|row| matches!(row, Row::Field(f) if *f == field))
+            })
+            .unwrap_or(1);
+        self.list.select(Some(idx));
+    }
+
+    fn selected(&self) -> Option<Field> {
+        match self.list.selected().and_then(|i| self.rows.get(i)) {
+            Some(Row::Field(field)) => Some(field.clone()),
+            _ => None,
+        }
+    }
+
+    /// Перемещает выбор на `delta` параметров, пропуская заголовки разделов
+    fn step(&mut self, delta: isize) {
+        let mut idx = self.list.selected().unwrap_or(0) as isize;
+        loop {
+            idx += delta;
+            if idx < 0 || idx >= self.rows.len() as isize {
+                return;
+            }
+            if let Row::Field(_) = self.rows[idx as usize] {
+                self.list.select(Some(idx as usize));
+                return;
+            }
+        }
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+
+            let Input::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+
+            let mode = std::mem::replace(&mut self.mode, Mode::Browse);
+            self.mode = match mode {
+                Mode::Browse => match self.handle_browse(key) {
+                    Some(mode) => mode,
+                    None => return Ok(()),
+                },
+                Mode::Edit { field, input } => self.handle_edit(key, field, input),
+                Mode::Pick {
+                    field,
+                    options,
+                    list,
+                } => self.handle_pick(key, field, options, list),
+                Mode::ConfirmQuit => match key.code {
+                    KeyCode::Char('y') | KeyCode::Char('Y') if self.save() => return Ok(()),
+                    KeyCode::Char('n') | KeyCode::Char('N') => return Ok(()),
+                    _ => Mode::Browse,
+                },
+            };
+        }
+    }
+
+    /// Возвращает `None` для выхода из редактора
+    fn handle_browse(&mut self, key: KeyEvent) -> Option<Mode> {
+        self.status = None;
+
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => self.step(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.step(1),
+            KeyCode::Enter | KeyCode::Char('e') => {
+                if let Some(field) = self.selected() {
+                    return Some(self.start_edit(field));
+                }
+            }
+            KeyCode::Char('a') => return Some(self.start_edit(Field::AddEnv)),
+            KeyCode::Char('d') | KeyCode::Delete => {
+                if let Some(Field::Env(key)) = self.selected() {
+                    self.conf.env.remove(&key);
+                    self.modified = true;
+                    let idx = self.list.selected();
+                    self.rebuild_rows(None);
+                    self.list.select(idx.map(|i| i.min(self.rows.len() - 1)));
+                    self.status = Some((tr!("menuconfig.removed", name = key), false));
+                }
+            }
+            KeyCode::Char('s') => {
+                self.save();
+            }
+            KeyCode::Char('q') | KeyCode::Esc => {
+                if !self.modified {
+                    return None;
+                }
+                return Some(Mode::ConfirmQuit);
+            }
+            _ => {}
+        }
+
+        Some(Mode::Browse)
+    }
+
+    fn start_edit(&self, field: Field) -> Mode {
+        let value = field.value(&self.conf);
+
+        match field.options(&self.conf) {
+            Some(options) => {
+                let current = match value.as_str() {
+                    "" => NONE,
+                    value => value,
+                };
+                let idx = options
+                    .iter()
+                    .position(|o| o == current)
+                    .or(options.iter().position(|o| o == CUSTOM));
+                Mode::Pick {
+                    field,
+                    options,
+                    list: ListState::default().with_selected(idx.or(Some(0))),
+                }
+            }
+            None => Mode::Edit {
+                field,
+                input: TextInput::new(value),
+            },
+        }
+    }
+
+    fn handle_edit(&mut self, key: KeyEvent, field: Field, mut input: TextInput) -> Mode {
+        match key.code {
+            KeyCode::Esc => Mode::Browse,
+            KeyCode::Enter => self.apply(field, input.text),
+            code => {
+                input.handle(code);
+                Mode::Edit { field, input }
+            }
+        }
+    }
+
+    fn handle_pick(
+        &mut self,
+        key: KeyEvent,
+        field: Field,
+        options: Vec<String>,
+        mut list: ListState,
+    ) -> Mode {
+        match key.code {
+            KeyCode::Esc => return Mode::Browse,
+            KeyCode::Up | KeyCode::Char('k') => list.select_previous(),
+            KeyCode::Down | KeyCode::Char('j') => list.select_next(),
+            KeyCode::Enter => {
+                let choice = list
+                    .selected()
+                    .and_then(|i| options.get(i))
+                    .cloned()
+                    .unwrap_or_default();
+                if choice == CUSTOM {
+                    let input = TextInput::new(field.value(&self.conf));
+                    return Mode::Edit { field, input };
+                }
+                return self.apply(field, choice);
+            }
+            _ => {}
+        }
+
+        Mode::Pick {
+            field,
+            options,
+            list,
+        }
+    }
+
+    /// Устанавливает значение параметра. При ошибке остаётся в режиме
+    /// редактирования.
+    fn apply(&mut self, field: Field, value: String) -> Mode {
+        match field.set(&mut self.conf, value.clone()) {
+            Ok(()) => {
+                self.modified = true;
+                match field {
+                    // после добавления переменной вводится её значение
+                    Field::AddEnv => {
+                        let field = Field::Env(value.trim().to_string());
+                        self.rebuild_rows(Some(field.clone()));
+                        Mode::Edit {
+                            field,
+                            input: TextInput::default(),
+                        }
+                    }
+                    _ => Mode::Browse,
+                }
+            }
+            Err(why) => {
+                self.status = Some((why, true));
+                Mode::Edit {
+                    field,
+                    input: TextInput::new(value),
+                }
+            }
+        }
+    }
+
+    /// Сохраняет конфигурацию, если параметры целевой системы корректны
+    fn save(&mut self) -> bool {
+        if !check_env_default(&self.conf.env_default).is_empty() {
+            self.status = Some((tr!("menuconfig.invalid_target"), true));
+            return false;
+        }
+
+        match self.conf.write(&self.path) {
+            Ok(()) => {
+                self.modified = false;
+                self.status = Some((tr!("menuconfig.saved", path = self.path.display()), false));
+                true
+            }
+            Err(why) => {
+                self.status = Some((tr!("menuconfig.save_failed", why = why), true));
+                false
+            }
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let issues = check_env_default(&self.conf.env_default);
+        let issues_height = match issues.len() {
+            0 => 0,
+            n => n as u16 + 2,
+        };
+
+        let [header, body, issues_area, status, footer] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(5),
+            Constraint::Length(issues_height),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let title = match self.modified {
+            true => tr!("menuconfig.title_modified", path = self.path.display()),
+            false => tr!("menuconfig.title", path = self.path.display()),
+        };
+        frame.render_widget(
+            Paragraph::new(format!(" {title}")).bold().reversed(),
+            header,
+        );
+
+        let items = self
+            .rows
+            .iter()
+            .map(|row| match row {
+                Row::Header(title) => ListItem::new(Line::from(title.as_str()).bold()),
+                Row::Field(Field::AddEnv) => ListItem::new(
+                    Line::from(format!("  {}", tr!("menuconfig.add_var"))).fg(Color::DarkGray),
+                ),
+                Row::Field(field) => {
+                    let value = match field.value(&self.conf) {
+                        value if value.is_empty() => Span::from(NONE).fg(Color::DarkGray),
+                        value => Span::from(value).fg(Color::Cyan),
+                    };
+                    ListItem::new(Line::from(vec![
+                        Span::from(format!("  {:<LABEL_WIDTH$} ", field.label())),
+                        value,
+                    ]))
+                }
+            })
+            .collect::<Vec<_>>();
+        let list = List::new(items)
+            .block(Block::bordered())
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, body, &mut self.list);
+
+        let issues = issues
+            .iter()
+            .map(|issue| Line::from(format!("- {issue}")))
+            .collect::<Vec<_>>();
+        frame.render_widget(
+            Paragraph::new(issues)
+                .fg(Color::Red)
+                .block(Block::bordered().title(format!(" {} ", tr!("menuconfig.issues")))),
+            issues_area,
+        );
+
+        match &self.mode {
+            Mode::Edit { field, input } => {
+                let prompt = format!("{}: ", field.label());
+                let x = status.x + (prompt.chars().count() + input.cursor) as u16;
+                frame.render_widget(Paragraph::new(format!("{prompt}{}", &input.text)), status);
+                frame.set_cursor_position((x.min(status.right().saturating_sub(1)), status.y));
+            }
+            Mode::ConfirmQuit => frame.render_widget(
+                Paragraph::new(tr!("menuconfig.confirm_quit")).fg(Color::Yellow),
+                status,
+            ),
+            _ => {
+                if let Some((msg, is_err)) = &self.status {
+                    let color = if *is_err { Color::Red } else { Color::Green };
+                    frame.render_widget(Paragraph::new(msg.as_str()).fg(color), status);
+                }
+            }
+        }
+        if let (Mode::Edit { .. }, Some((msg, true))) = (&self.mode, &self.status) {
+            frame.render_widget(Paragraph::new(msg.as_str()).fg(Color::Red), issues_area);
+        }
+
+        let keys = match self.mode {
+            Mode::Browse => tr!("menuconfig.keys_browse"),
+            Mode::Edit { .. } => tr!("menuconfig.keys_edit"),
+            Mode::Pick { .. } => tr!("menuconfig.keys_pick"),
+            Mode::ConfirmQuit => String::new(),
+        };
+        frame.render_widget(Paragraph::new(keys).fg(Color::DarkGray), footer);
+
+        if let Mode::Pick {
+            field,
+            options,
+            list,
+        } = &mut self.mode
+        {
+            let area = popup(body, options.len() as u16 + 2);
+            let items = options
+                .iter()
+                .map(|o| ListItem::new(o.as_str()))
+                .collect::<Vec<_>>();
+            let widget = List::new(items)
+                .block(Block::bordered().title(format!(" {} ", field.label())))
+                .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+            frame.render_widget(Clear, area);
+            frame.render_stateful_widget(widget, area, list);
+        }
+    }
+}
+
+/// Область всплывающего списка по центру `area`
+fn popup(area: Rect, height: u16) -> Rect {
+    let width = 40.min(area.width);
+    let height = height.min(area.height);
+
+    Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    }
+}
@@ -0,0 +1,272 @@
+//! Managed mounts of virtual file systems for the chroot stage
+//!
+//! Все точки монтирования, созданные через [`Mounts`], размонтируются в
+//! обратном порядке при уничтожении объекта: как при успешном завершении,
+//! так и при ошибке или прерывании сборки (Ctrl-C). Оставшиеся после
+//! аварийного завершения точки монтирования удаляются перед следующим
+//! монтированием, т.к. `rm -rf` по дереву с примонтированными `/dev` и
+//! `/proc` опасен для хост-системы.
+
+use anyhow::{Error, Result};
+use colored::Colorize;
+use std::{
+    ffi::CString,
+    fs,
+    io::ErrorKind,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    ptr,
+};
+
+use crate::error::AlfaError;
+use crate::process_msg;
+use crate::qemu::Qemu;
+use crate::tr;
+use crate::tui::process_msg_result_err;
+
+/// Набор точек монтирования, размонтируемых при уничтожении
+#[derive(Debug, Default)]
+pub struct Mounts {
+    targets: Vec<PathBuf>,
+
+    /// Пустые файлы, созданные в качестве точек монтирования
+    files: Vec<PathBuf>,
+}
+
+impl Mounts {
+    /// Монтирует виртуальные файловые системы хоста в `root` (аналогично
+    /// главе 7.3 руководства LFS), а также исходный код и сборочные скрипты
+    /// из `build_dir` в `root/alfa/{src,scripts}` и, при необходимости,
+    /// интерпретатор qemu-user
+    pub fn chroot<P: AsRef<Path>, Q: AsRef<Path>>(
+        root: P,
+        build_dir: Q,
+        qemu: Option<&Qemu>,
+    ) -> Result<Self> {
+        let (root, build_dir) = (root.as_ref(), build_dir.as_ref());
+        umount_stale(root)?;
+
+        let mut mounts = Self::default();
+        for dir in ["src", "scripts"] {
+            mounts.bind(build_dir.join(dir), root.join("alfa").join(dir))?;
+        }
+        mounts.bind("/dev", root.join("dev"))?;
+        mounts.bind("/dev/pts", root.join("dev/pts"))?;
+        mounts.bind("/proc", root.join("proc"))?;
+        // в пространстве имён пользователей (режим rootless) sysfs нельзя
+        // смонтировать без отдельного сетевого пространства имён
+        if mounts.mount("sysfs", root.join("sys"), "sysfs").is_err() {
+            mounts.bind("/sys", root.join("sys"))?;
+        }
+        mounts.mount("tmpfs", root.join("run"), "tmpfs")?;
+
+        if let Some(qemu) = qemu.filter(|q| !q.fix_binary) {
+            let target = root.join(
+                qemu.interpreter
+                    .strip_prefix("/")
+                    .unwrap_or(&qemu.interpreter),
+            );
+            mounts.bind_file(&qemu.interpreter, target)?;
+        }
+
+        Ok(mounts)
+    }
+
+    pub fn bind<P: AsRef<Path>, Q: AsRef<Path>>(&mut self, src: P, target: Q) -> Result<()> {
+        let target = target.as_ref();
+        fs::create_dir_all(target)?;
+
+        process_msg!(tr!(
+            "mounts.bind",
+            src = src.as_ref().display().to_string().dimmed(),
+            target = target.display().to_string().dimmed()
+        ));
+        let rslt = mount(
+            Some(src.as_ref().as_os_str().as_bytes()),
+            target,
+            None,
+            libc::MS_BIND | libc::MS_REC,
+        );
+        process_msg_result_err(rslt.is_ok(), rslt.as_ref().err());
+        rslt?;
+        self.targets.push(target.to_path_buf());
+
+        Ok(())
+    }
+
+    /// Монтирует файл `src` поверх `target`; если `target` не существует,
+    /// то создаётся пустой файл, который удаляется после размонтирования
+    pub fn bind_file<P: AsRef<Path>, Q: AsRef<Path>>(&mut self, src: P, target: Q) -> Result<()> {
+        let target = target.as_ref();
+        if !target.exists() {
+            if let Some(dir) = target.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            fs::File::create(target)?;
+            self.files.push(target.to_path_buf());
+        }
+
+        process_msg!(tr!(
+            "mounts.bind",
+            src = src.as_ref().display().to_string().dimmed(),
+            target = target.display().to_string().dimmed()
+        ));
+        let rslt = mount(
+            Some(src.as_ref().as_os_str().as_bytes()),
+            target,
+            None,
+            libc::MS_BIND,
+        );
+        process_msg_result_err(rslt.is_ok(), rslt.as_ref().err());
+        rslt?;
+        self.targets.push(target.to_path_buf());
+
+        Ok(())
+    }
+
+    pub fn mount<P: AsRef<Path>>(&mut self, src: &str, target: P, fstype: &str) -> Result<()> {
+        let target = target.as_ref();
+        fs::create_dir_all(target)?;
+
+        process_msg!(tr!(
+            "mounts.mount",
+            fstype = fstype,
+            target = target.display().to_string().dimmed()
+        ));
+        let rslt = mount(Some(src), target, Some(fstype), 0);
+        process_msg_result_err(rslt.is_ok(), rslt.as_ref().err());
+        rslt?;
+        self.targets.push(target.to_path_buf());
+
+        Ok(())
+    }
+}
+
+impl Drop for Mounts {
+    fn drop(&mut self) {
+        while let Some(target) = self.targets.pop() {
+            process_msg!(tr!(
+                "mounts.unmount",
+                target = target.display().to_string().dimmed()
+            ));
+            let rslt = umount(&target);
+            process_msg_result_err(rslt.is_ok(), rslt.err());
+        }
+        for file in self.files.drain(..) {
+            let _ = fs::remove_file(file);
+        }
+    }
+}
+
+/// Размонтирует точки монтирования внутри `root`, оставшиеся после
+/// аварийного завершения ALFA
+pub fn umount_stale<P: AsRef<Path>>(root: P) -> Result<()> {
+    let stale = mounted_under(root)?;
+    // сначала вложенные точки монтирования
+    for target in stale.iter().rev() {
+        process_msg!(tr!(
+            "mounts.unmount_stale",
+            target = target.display().to_string().dimmed()
+        ));
+        let rslt = umount(target);
+        process_msg_result_err(rslt.is_ok(), rslt.as_ref().err());
+        rslt?;
+    }
+
+    Ok(())
+}
+
+/// Точки монтирования внутри `root` в порядке монтирования
+pub fn mounted_under<P: AsRef<Path>>(root: P) -> Result<Vec<PathBuf>> {
+    let root = match fs::canonicalize(root.as_ref()) {
+        Ok(root) => root,
+        Err(_) => return Ok(Vec::new()),
+    };
+    let mounts = fs::read_to_string("/proc/self/mounts")?;
+
+    Ok(mounts
+        .lines()
+        .filter_map(|line| line.split_whitespace().nth(1))
+        .map(|target| PathBuf::from(unescape(target)))
+        .filter(|target| target.starts_with(&root) && target != &root)
+        .collect())
+}
+
+/// Пробелы и другие символы в `/proc/self/mounts` записываются как `\ooo`
+pub fn unescape(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut rslt = Vec::new();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'\\' && i + 4 <= bytes.len() {
+            let code = std::str::from_utf8(&bytes[i + 1..i + 4])
+                .ok()
+                .and_then(|code| u8::from_str_radix(code, 8).ok());
+            if let Some(c) = code {
+                rslt.push(c);
+                i += 4;
+                continue;
+            }
+        }
+        rslt.push(bytes[i]);
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&rslt).to_string()
+}
+
+pub fn mount<S: AsRef<[u8]>, P: AsRef<Path>>(
+    src: Option<S>,
+    target: P,
+    fstype: Option<&str>,
+    flags: libc::c_ulong,
+) -> Result<()> {
+    let target = target.as_ref();
+    let src = src.map(|s| CString::new(s.as_ref())).transpose()?;
+    let ctarget = CString::new(target.as_os_str().as_bytes())?;
+    let fstype = fstype.map(CString::new).transpose()?;
+
+    // SAFETY: все указатели - корректные C-строки либо NULL
+    let rslt = unsafe {
+        libc::mount(
+            src.as_ref().map_or(ptr::null(), |s| s.as_ptr()),
+            ctarget.as_ptr(),
+            fstype.as_ref().map_or(ptr::null(), |s| s.as_ptr()),
+            flags,
+            ptr::null(),
+        )
+    };
+    if rslt != 0 {
+        let why = std::io::Error::last_os_error();
+        let msg = tr!("mounts.mount_failed", target = target.display(), why = why);
+        return Err(match why.kind() {
+            ErrorKind::PermissionDenied => AlfaError::Privilege(msg).into(),
+            _ => Error::msg(msg),
+        });
+    }
+
+    Ok(())
+}
+
+/// Размонтирует `target`; если файловая система занята, то выполняется
+/// отложенное размонтирование (`umount -l`)
+pub fn umount<P: AsRef<Path>>(target: P) -> Result<()> {
+    let target = target.as_ref();
+    let ctarget = CString::new(target.as_os_str().as_bytes())?;
+
+    // SAFETY: `ctarget` - корректная C-строка
+    if unsafe { libc::umount2(ctarget.as_ptr(), 0) } == 0 {
+        return Ok(());
+    }
+    // SAFETY: см. выше
+    if unsafe { libc::umount2(ctarget.as_ptr(), libc::MNT_DETACH) } == 0 {
+        return Ok(());
+    }
+
+    Err(Error::msg(tr!(
+        "mounts.unmount_failed",
+        target = target.display(),
+        why = std::io::Error::last_os_error()
+    )))
+}
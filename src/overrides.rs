@@ -0,0 +1,219 @@
+//! Non-interactive configuration: answers files, `ALFA_*` environment
+//! variables and `--set key=value` overrides
+
+use anyhow::Result;
+use std::{env, fs, path::Path};
+use toml::{self, map::Map, Value};
+
+use crate::error::AlfaError;
+use crate::tr;
+
+/// Секции `.config.toml`, которые можно переопределить через переменные
+/// окружения `ALFA_<SECTION>_<KEY>`. Порядок важен: `ENV_DEFAULT_` должен
+/// проверяться раньше, чем `ENV_`.
+const ENV_SECTIONS: &[(&str, &str)] = &[
+    ("SYSTEM_", "system"),
+    ("ENV_DEFAULT_", "env_default"),
+    ("ENV_", "env"),
+];
+
+/// Источники значений для неинтерактивной генерации `.config.toml`.
+/// Применяются в порядке: файл ответов, переменные окружения, `--set`
+/// (см. [`Layers::push_overrides`](crate::layers::Layers::push_overrides)).
+#[derive(Debug, Default)]
+pub struct Overrides {
+    /// TOML- или JSON-файл с ответами на вопросы мастера настройки
+    pub answers: Option<String>,
+
+    /// Значения вида `system.name=ALFA`
+    pub set: Vec<String>,
+
+    /// Учитывать ли переменные окружения `ALFA_*`
+    pub use_env: bool,
+}
+
+/// Читает файл ответов; формат определяется по расширению (`*.json` или TOML)
+pub fn read_answers<P: AsRef<Path>>(pth: P) -> Result<Value> {
+    let contents = fs::read_to_string(&pth)?;
+    let is_json = pth.as_ref().extension().is_some_and(|ext| ext == "json");
+
+    if is_json {
+        let json: serde_json::Value = serde_json::from_str(&contents)?;
+        Ok(Value::try_from(json)?)
+    } else {
+        Ok(toml::from_str(&contents)?)
+    }
+}
+
+/// Рекурсивно сливает таблицу `other` в `base` (ключ за ключом)
+pub fn merge(base: &mut Value, other: Value) {
+    match (base, other) {
+        (Value::Table(base), Value::Table(other)) => {
+            for (k, v) in other {
+                match base.get_mut(&k) {
+                    Some(old) => merge(old, v),
+                    None => {
+                        base.insert(k, v);
+                    }
+                }
+            }
+        }
+        (base, other) => *base = other,
+    }
+}
+
+/// Устанавливает значение по ключу вида `section.key`. Тип значения
+/// определяется по уже имеющемуся значению; новые ключи считаются строками.
+/// Пустое значение удаляет ключ.
+pub fn set(root: &mut Value, key: &str, raw: &str) -> Result<()> {
+    let mut parts = key.split('.').peekable();
+    let mut node = root;
+
+    while let Some(part) = parts.next() {
+        if part.is_empty() {
+            return Err(AlfaError::Config(tr!("overrides.bad_key", key = key)).into());
+        }
+
+        let table = node
+            .as_table_mut()
+            .ok_or(AlfaError::Config(tr!("overrides.not_table", key = key)))?;
+
+        if parts.peek().is_none() {
+            if raw.is_empty() {
+                table.remove(part);
+                return Ok(());
+            }
+            let new = parse_as(table.get(part), raw).ok_or(AlfaError::Config(tr!(
+                "overrides.bad_value",
+                value = raw,
+                key = key
+            )))?;
+            table.insert(part.to_string(), new);
+            return Ok(());
+        }
+
+        node = table
+            .entry(part.to_string())
+            .or_insert(Value::Table(Map::new()));
+    }
+
+    Ok(())
+}
+
+fn parse_as(old: Option<&Value>, raw: &str) -> Option<Value> {
+    match old {
+        Some(Value::Integer(_)) => raw.parse().ok().map(Value::Integer),
+        Some(Value::Float(_)) => raw.parse().ok().map(Value::Float),
+        Some(Value::Boolean(_)) => raw.parse().ok().map(Value::Boolean),
+        Some(Value::Array(_)) | Some(Value::Table(_)) => {
+            let doc: Map<String, Value> = toml::from_str(&format!("v = {raw}")).ok()?;
+            doc.get("v").cloned()
+        }
+        _ => Some(Value::String(raw.to_string())),
+    }
+}
+
+/// Возвращает переопределения из переменных окружения `ALFA_*` в виде пар
+/// (ключ, значение), e.g. `ALFA_ENV_DEFAULT_LFA_ARCH` -> `env_default.lfa_arch`
+pub fn env_overrides() -> Vec<(String, String)> {
+    env_overrides_from(env::vars())
+}
+
+/// То же, что [`env_overrides`], но для заданного набора переменных
+pub fn env_overrides_from<I: IntoIterator<Item = (String, String)>>(
+    vars: I,
+) -> Vec<(String, String)> {
+    let mut overrides = Vec::new();
+
+    for (var, val) in vars {
+        let Some(name) = var.strip_prefix("ALFA_") else {
+            continue;
+        };
+
+        for (prefix, section) in ENV_SECTIONS {
+            if let Some(key) = name.strip_prefix(prefix) {
+                if key.is_empty() {
+                    break;
+                }
+                // имена переменных в секции `env` сохраняются как есть
+                let key = if *section == "env" {
+                    key.to_string()
+                } else {
+                    key.to_lowercase()
+                };
+                overrides.push((format!("{section}.{key}"), val));
+                break;
+            }
+        }
+    }
+    overrides.sort();
+
+    overrides
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn root() -> Value {
+        toml::from_str(
+            r#"
+            [system]
+            name = "ALFA"
+
+            [timing]
+            jobs = 4
+            fast = false
+            "#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn set_dotted_keys() {
+        let mut value = root();
+        set(&mut value, "system.name", "LFA").unwrap();
+        set(&mut value, "timing.jobs", "8").unwrap();
+        set(&mut value, "timing.fast", "true").unwrap();
+        set(&mut value, "env.MAKEFLAGS", "-j8").unwrap();
+
+        assert_eq!(value["system"]["name"].as_str(), Some("LFA"));
+        assert_eq!(value["timing"]["jobs"].as_integer(), Some(8));
+        assert_eq!(value["timing"]["fast"].as_bool(), Some(true));
+        assert_eq!(value["env"]["MAKEFLAGS"].as_str(), Some("-j8"));
+
+        set(&mut value, "system.name", "").unwrap();
+        assert!(value["system"].get("name").is_none());
+    }
+
+    #[test]
+    fn set_errors() {
+        let mut value = root();
+        assert!(set(&mut value, ".name", "x").is_err());
+        assert!(set(&mut value, "system..name", "x").is_err());
+        assert!(set(&mut value, "system.name.first", "x").is_err());
+        assert!(set(&mut value, "timing.jobs", "many").is_err());
+        assert!(set(&mut value, "timing.fast", "yes").is_err());
+    }
+
+    #[test]
+    fn env_names() {
+        let vars = [
+            ("ALFA_SYSTEM_NAME", "LFA"),
+            ("ALFA_ENV_DEFAULT_LFA_ARCH", "armv8.2-a"),
+            ("ALFA_ENV_MAKEFLAGS", "-j8"),
+            ("ALFA_ENV_", "ignored"),
+            ("ALFA_OTHER", "ignored"),
+            ("PATH", "/bin"),
+        ]
+        .map(|(var, val)| (var.to_string(), val.to_string()));
+
+        let expected = [
+            ("env.MAKEFLAGS", "-j8"),
+            ("env_default.lfa_arch", "armv8.2-a"),
+            ("system.name", "LFA"),
+        ]
+        .map(|(key, val)| (key.to_string(), val.to_string()));
+        assert_eq!(env_overrides_from(vars), expected);
+    }
+}
@@ -0,0 +1,214 @@
+//! Package list queries (`alfa list`, `alfa info`, `alfa search`)
+//!
+//! Сведения о пакетах берутся из `packages.toml` и дополняются сборочными
+//! инструкциями из `pkg_order.toml`, которые относятся к пакету (см.
+//! [`PackageList::find`]). Описание и примечания выводятся на языке
+//! интерфейса (см. [`crate::i18n::Localized`]).
+
+use anyhow::Result;
+use colored::Colorize;
+use serde::Serialize;
+
+use crate::build_meta::{Package, PackageList, PackageOrder};
+use crate::events::{emit, is_human, Event};
+use crate::tr;
+
+/// Максимальная длина описания в списке пакетов (в символах)
+const DESCRIPTION_WIDTH: usize = 60;
+
+/// Пакет вместе с его сборочными инструкциями
+#[derive(Debug, Serialize)]
+pub struct PackageEntry<'a> {
+    pub name: &'a str,
+    pub version: &'a str,
+    pub description: Option<&'a str>,
+    pub home_page: Option<&'a str>,
+    pub license: Option<&'a str>,
+    pub download: &'a str,
+    pub md5: &'a str,
+    pub notes: Option<&'a str>,
+
+    /// Сборочные инструкции пакета (e.g. `cross-compiler/linux-headers`)
+    pub instructions: Vec<String>,
+
+    #[serde(skip)]
+    package: &'a Package,
+}
+
+impl<'a> PackageEntry<'a> {
+    fn new(name: &'a str, package: &'a Package) -> Self {
+        Self {
+            name,
+            version: &package.version,
+            description: package.description.as_ref().map(|d| d.get()),
+            home_page: package.home_page.as_deref(),
+            license: package.license.as_deref(),
+            download: &package.download,
+            md5: &package.md5,
+            notes: package.notes.as_ref().map(|n| n.get()),
+            instructions: Vec::new(),
+            package,
+        }
+    }
+
+    /// Все пакеты списка (в алфавитном порядке) с их сборочными
+    /// инструкциями из `order`
+    pub fn collect(packages: &'a PackageList, order: Option<&PackageOrder>) -> Result<Vec<Self>> {
+        let mut entries = packages
+            .package
+            .iter()
+            .map(|(name, pkg)| Self::new(name, pkg))
+            .collect::<Vec<_>>();
+        entries.sort_by_key(|e| e.name.to_lowercase());
+
+        if let Some(order) = order {
+            for (pth, instr) in order.packages.iter().zip(order.instructions()?) {
+                let Some((name, _)) = packages.find(&instr) else {
+                    continue;
+                };
+                if let Some(entry) = entries.iter_mut().find(|e| e.name == name) {
+                    entry.instructions.push(pth.clone());
+                }
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// Пакет по имени (регистр не учитывается) или по имени его сборочной
+    /// инструкции (e.g. `linux-headers` для пакета `Linux`)
+    pub fn find<'b>(entries: &'b [Self], name: &str) -> Option<&'b Self> {
+        let name = name.to_lowercase();
+        entries
+            .iter()
+            .find(|e| e.name.to_lowercase() == name)
+            .or_else(|| {
+                entries.iter().find(|e| {
+                    e.instructions.iter().any(|pth| {
+                        let instr = pth.rsplit('/').next().unwrap_or(pth);
+                        pth.to_lowercase() == name || instr.to_lowercase() == name
+                    })
+                })
+            })
+    }
+
+    /// Встречается ли `term` (регистр не учитывается) в имени, описании (на
+    /// любом языке), домашней странице, лицензии, примечаниях или именах
+    /// сборочных инструкций пакета
+    pub fn matches(&self, term: &str) -> bool {
+        let term = term.to_lowercase();
+        let pkg = self.package;
+
+        let mut fields = vec![self.name, self.version];
+        fields.extend(pkg.description.iter().flat_map(|d| d.variants()));
+        fields.extend(pkg.notes.iter().flat_map(|n| n.variants()));
+        fields.extend(self.home_page);
+        fields.extend(self.license);
+        fields.extend(self.instructions.iter().map(|s| s.as_str()));
+
+        fields.iter().any(|f| f.to_lowercase().contains(&term))
+    }
+}
+
+/// Выводит список пакетов в виде таблицы (события `package` в режиме JSON)
+pub fn print_list(entries: &[PackageEntry]) {
+    if !is_human() {
+        for entry in entries {
+            emit(&Event::Package(entry));
+        }
+        return;
+    }
+
+    let headers = [
+        tr!("pkginfo.package"),
+        tr!("pkginfo.version"),
+        tr!("pkginfo.license"),
+        tr!("pkginfo.description"),
+    ];
+    let width = |header: &str, col: &dyn Fn(&PackageEntry) -> usize| {
+        entries
+            .iter()
+            .map(col)
+            .max()
+            .unwrap_or(0)
+            .max(header.chars().count())
+    };
+    let w_name = width(&headers[0], &|e| e.name.chars().count());
+    let w_ver = width(&headers[1], &|e| e.version.chars().count());
+    let w_lic = width(&headers[2], &|e| e.license.unwrap_or("-").chars().count());
+
+    println!(
+        "{}",
+        format!(
+            "{:<w_name$}  {:<w_ver$}  {:<w_lic$}  {}",
+            &headers[0], &headers[1], &headers[2], &headers[3]
+        )
+        .bold()
+    );
+    for entry in entries {
+        println!(
+            "{:<w_name$}  {:<w_ver$}  {:<w_lic$}  {}",
+            entry.name,
+            entry.version,
+            entry.license.unwrap_or("-"),
+            truncate(entry.description.unwrap_or(""), DESCRIPTION_WIDTH).dimmed()
+        );
+    }
+}
+
+/// Выводит все сведения о пакете (событие `package` в режиме JSON)
+pub fn print_info(entry: &PackageEntry) {
+    if !is_human() {
+        emit(&Event::Package(entry));
+        return;
+    }
+
+    let pkg = entry.package;
+    let size = match (pkg.size_source, pkg.size_build) {
+        (None, None) => None,
+        (source, build) => Some(tr!(
+            "pkginfo.size_value",
+            source = source.map_or("?".to_string(), |s| s.to_string()),
+            build = build.map_or("?".to_string(), |s| s.to_string())
+        )),
+    };
+    let instructions = match entry.instructions.is_empty() {
+        true => None,
+        false => Some(entry.instructions.join(", ")),
+    };
+
+    let rows = [
+        (tr!("pkginfo.name"), Some(entry.name.to_string())),
+        (tr!("pkginfo.version"), Some(entry.version.to_string())),
+        (
+            tr!("pkginfo.description"),
+            entry.description.map(String::from),
+        ),
+        (tr!("pkginfo.home_page"), entry.home_page.map(String::from)),
+        (tr!("pkginfo.license"), entry.license.map(String::from)),
+        (tr!("pkginfo.download"), Some(entry.download.to_string())),
+        (tr!("pkginfo.md5"), Some(entry.md5.to_string())),
+        (tr!("pkginfo.size"), size),
+        (tr!("pkginfo.instructions"), instructions),
+        (tr!("pkginfo.notes"), entry.notes.map(String::from)),
+    ];
+    let width = rows
+        .iter()
+        .map(|(l, _)| l.chars().count())
+        .max()
+        .unwrap_or(0)
+        + 1;
+
+    for (label, value) in rows {
+        if let Some(value) = value {
+            println!("{:<width$} {value}", format!("{label}:").bold());
+        }
+    }
+}
+
+fn truncate(s: &str, width: usize) -> String {
+    match s.chars().count() > width {
+        true => format!("{}...", s.chars().take(width - 3).collect::<String>()),
+        false => s.to_string(),
+    }
+}
@@ -1,88 +1,199 @@
 //! Preparing host system for ALFA building
 
-use anyhow::Result;
+use anyhow::{Error, Result};
 use colored::Colorize;
 use std::fs::create_dir_all;
-use std::process::Command;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
 
+use crate::config::Config;
+use crate::error::AlfaError;
+use crate::logging;
 use crate::process_msg;
-// use crate::config::Config;
-use crate::profile::Profile;
-use crate::tui::process_msg_result_err;
+use crate::profile::{Profile, USER_NAME_MAX};
+use crate::shell_env::ShellEnv;
+use crate::tr;
+use crate::tui::{process_msg_result, process_msg_result_err, process_msg_result_skip};
 
 pub struct Prepare<'a> {
     pub profile: &'a Profile,
+
+    /// Режим rootless (см. [`crate::rootless`]): сборочный пользователь не
+    /// создаётся, домашней директорией служит `build_dir/home`
+    pub rootless: bool,
 }
 
 impl<'a> Prepare<'a> {
     pub fn create_alfa_dirs(&self) -> Result<()> {
         // create root dir
-        process_msg!(
-            "Create root directory '{}'",
-            &self.profile.build_dir.dimmed()
-        );
+        process_msg!(tr!(
+            "prepare.create_root_dir",
+            path = self.profile.build_dir.dimmed()
+        ));
         let rslt = create_dir_all(&self.profile.build_dir);
-        process_msg_result_err(
-            rslt.is_ok(),
-            if let Err(err) = rslt { Some(err) } else { None },
-        );
+        process_msg_result_err(rslt.is_ok(), rslt.err());
 
         // create other dirs
         for i in ["lfa", "src", "scripts"] {
             let dir = format!("{}/{}", &self.profile.build_dir, i);
-            process_msg!("Create subdirectory '{}'", &dir.dimmed());
+            process_msg!(tr!("prepare.create_subdir", path = dir.dimmed()));
             let rslt = create_dir_all(&dir);
-            process_msg_result_err(
-                rslt.is_ok(),
-                if let Err(err) = rslt { Some(err) } else { None },
-            );
+            process_msg_result_err(rslt.is_ok(), rslt.err());
         }
 
         Ok(())
     }
 
+    /// Создаёт сборочного пользователя и одноимённую группу. Пользователь
+    /// создаётся без пароля и заблокированным (вход по паролю невозможен).
+    /// Если пользователь или группа уже существуют, они используются
+    /// повторно; учётная запись существующего пользователя не изменяется.
     pub fn create_user(&self) -> Result<()> {
-        let grp = Command::new("/sbin/groupadd")
-            .arg(&self.profile.user_name)
-            .status()?
-            .success();
-        if !grp {
-            return Err(anyhow::Error::msg(format!(
-                "Failed to create group '{}'",
-                &self.profile.user_name.dimmed()
+        let name = &self.profile.user_name;
+        // пользователь мог быть создан более старой версией ALFA, которая
+        // не ограничивала длину имени
+        let exists = user_exists(name)?;
+        if !exists && name.len() > USER_NAME_MAX {
+            return Err(Error::msg(tr!(
+                "prepare.user_name_too_long",
+                name = name,
+                max = USER_NAME_MAX
             )));
         }
 
-        let args = [
-            "-s",
-            "/bin/bash",
-            "-g",
-            &self.profile.user_name,
-            "-m",
-            "-k",
-            "/dev/null",
-            &self.profile.user_name,
-        ];
-        let usr = Command::new("/sbin/useradd").args(args).status()?.success();
-        if !usr {
-            return Err(anyhow::Error::msg(format!(
-                "Failed to create user '{}'",
-                &self.profile.user_name.dimmed()
-            )));
+        process_msg!(tr!("prepare.create_group", name = name.dimmed()));
+        if entry_exists("group", name)? {
+            process_msg_result_skip(tr!("prepare.exists"));
+        } else {
+            run("/sbin/groupadd", &[name])?;
+            process_msg_result(true);
         }
 
-        println!("\nSet password for new user:");
-        let pswd = Command::new("/sbin/passwd")
-            .arg(&self.profile.user_name)
-            .status()?
-            .success();
-        if !pswd {
-            return Err(anyhow::Error::msg(format!(
-                "Failed to set password for '{}' user",
-                &self.profile.user_name.dimmed()
-            )));
+        process_msg!(tr!("prepare.create_user", name = name.dimmed()));
+        if exists {
+            process_msg_result_skip(tr!("prepare.exists"));
+            return Ok(());
+        }
+        run(
+            "/sbin/useradd",
+            &["-s", "/bin/bash", "-g", name, "-m", "-k", "/dev/null", name],
+        )?;
+        process_msg_result(true);
+
+        process_msg!(tr!("prepare.lock_password", name = name.dimmed()));
+        run("/sbin/usermod", &["-L", name])?;
+        process_msg_result(true);
+
+        Ok(())
+    }
+
+    /// Записывает `.bash_profile` и `.bashrc` сборочного пользователя (см.
+    /// [`ShellEnv`]), если они отсутствуют или устарели
+    pub fn write_shell_env(&self, config: &Config) -> Result<ShellEnv> {
+        let home = if self.rootless {
+            let home = Path::new(&self.profile.build_dir).join("home");
+            create_dir_all(&home)?;
+            home
+        } else {
+            home_dir(&self.profile.user_name)?
+        };
+        let shell_env = ShellEnv::new(config, self.profile, home);
+
+        let changed = shell_env.write()?;
+        if !self.rootless {
+            let owner = format!("{0}:{0}", &self.profile.user_name);
+            for pth in changed {
+                run("chown", &[&owner, &pth.display().to_string()])?;
+            }
+        }
+
+        Ok(shell_env)
+    }
+
+    /// Передаёт сборочному пользователю права на поддиректории `build_dir`
+    pub fn chown_alfa_dirs(&self) -> Result<()> {
+        let owner = format!("{0}:{0}", &self.profile.user_name);
+
+        for i in ["lfa", "src", "scripts"] {
+            let dir = format!("{}/{}", &self.profile.build_dir, i);
+            process_msg!(tr!("prepare.chown", path = dir.dimmed()));
+            run("chown", &["-R", &owner, &dir])?;
+            process_msg_result(true);
         }
 
         Ok(())
     }
 }
+
+/// Проверяет, что процесс запущен от имени суперпользователя
+pub fn require_root(what: &str) -> Result<()> {
+    // SAFETY: функция не принимает указателей и всегда завершается успешно
+    if unsafe { libc::geteuid() } != 0 {
+        return Err(AlfaError::Privilege(tr!("prepare.requires_root", what = what)).into());
+    }
+
+    Ok(())
+}
+
+/// Домашняя директория пользователя из базы `passwd`
+pub fn home_dir(name: &str) -> Result<PathBuf> {
+    let out = Command::new("getent")
+        .args(["passwd", name])
+        .stderr(Stdio::null())
+        .output()?;
+    if !out.status.success() {
+        return Err(Error::msg(tr!("prepare.user_not_found", name = name)));
+    }
+
+    // name:password:UID:GID:GECOS:directory:shell
+    String::from_utf8_lossy(&out.stdout)
+        .trim()
+        .split(':')
+        .nth(5)
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .ok_or(Error::msg(tr!("prepare.no_home_dir", name = name)))
+}
+
+/// Проверяет, существует ли пользователь `name`
+pub fn user_exists(name: &str) -> Result<bool> {
+    entry_exists("passwd", name)
+}
+
+/// Проверяет наличие записи в базе `passwd` или `group`
+fn entry_exists(db: &str, name: &str) -> Result<bool> {
+    let status = Command::new("getent")
+        .args([db, name])
+        .stdout(Stdio::null())
+        .status()?;
+
+    // код 2 - запись не найдена
+    match status.code() {
+        Some(0) => Ok(true),
+        Some(2) => Ok(false),
+        _ => Err(Error::msg(tr!(
+            "prepare.lookup_failed",
+            name = name,
+            db = db,
+            status = status
+        ))),
+    }
+}
+
+fn run(prog: &str, args: &[&str]) -> Result<()> {
+    logging::debug(tr!(
+        "prepare.run",
+        command = format!("{prog} {}", args.join(" "))
+    ));
+    let status = Command::new(prog).args(args).status()?;
+    if !status.success() {
+        process_msg_result(false);
+        return Err(Error::msg(tr!(
+            "prepare.command_failed",
+            command = format!("{prog} {}", args.join(" ")),
+            status = status
+        )));
+    }
+
+    Ok(())
+}
@@ -7,9 +7,17 @@ use toml;
 use uuid::Uuid;
 
 use crate::config::System;
+use crate::schema::{self, Schema};
+
+/// Максимальная длина имени пользователя, допускаемая `useradd`
+pub const USER_NAME_MAX: usize = 32;
+
+/// Длина части UUID в имени сборочного пользователя (`lfa_` + 16 символов)
+const USER_NAME_UUID_LEN: usize = 16;
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Profile {
+    pub schema_version: i64,
     pub user_name: String,
     pub build_dir: String,
 }
@@ -32,7 +40,8 @@ impl Profile {
         let uuid = Uuid::new_v4().simple().to_string();
 
         Self {
-            user_name: format!("lfa_{}", &uuid),
+            schema_version: schema::PROFILE_VERSION,
+            user_name: format!("lfa_{}", &uuid[..USER_NAME_UUID_LEN]),
             build_dir: format!(
                 "/mnt/{}-{}-{}",
                 str_sanitizer(&sys.name),
@@ -46,15 +55,13 @@ impl Profile {
         let mut map = HashMap::new();
         map.insert("ALFA_USER", self.user_name.clone());
         map.insert("ALFA_BUILD_DIR", self.build_dir.clone());
+        map.insert("ALFA_SRC_DIR", format!("{}/src", &self.build_dir));
 
         map
     }
 
     pub fn read<P: AsRef<Path>>(pth: P) -> Result<Self> {
-        let contents = fs::read_to_string(&pth)?;
-        let data = toml::from_str(&contents)?;
-
-        Ok(data)
+        schema::read(Schema::Profile, pth)
     }
 
     pub fn write<P: AsRef<Path>>(&self, pth: P) -> Result<()> {
@@ -0,0 +1,208 @@
+//! Emulation of ARM chroot stages via qemu-user and binfmt_misc
+//!
+//! Хост-система, как правило, x86_64, поэтому для выполнения программ
+//! собираемой системы в chroot нужен статически собранный `qemu-aarch64`
+//! (или `qemu-arm`), зарегистрированный в `binfmt_misc`. Если регистрация
+//! выполнена без флага `F` (fix binary), то ядро ищет интерпретатор по пути
+//! внутри chroot, поэтому ALFA монтирует его туда на время сборки.
+
+use anyhow::{Error, Result};
+use std::{
+    env::consts::ARCH,
+    fs::{self, File},
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use crate::config::Bits;
+use crate::tr;
+
+const BINFMT_DIR: &str = "/proc/sys/fs/binfmt_misc";
+
+/// ELF: тип сегмента с путём до динамического загрузчика
+const PT_INTERP: u32 = 3;
+
+/// ELF: размер заголовка файла (ELF64; заголовок ELF32 короче)
+const EHDR_SIZE: usize = 0x40;
+
+/// `personality(2)`: 32-битное окружение Linux (`<linux/personality.h>`)
+const PER_LINUX32: libc::c_ulong = 0x0008;
+
+/// `personality(2)`: запрос текущей персональности без изменения
+const PER_QUERY: libc::c_ulong = 0xffff_ffff;
+
+#[derive(Debug)]
+pub struct Qemu {
+    /// Имя регистрации в `binfmt_misc` (`qemu-aarch64`, `qemu-arm`)
+    pub name: String,
+
+    /// Путь до интерпретатора, указанный при регистрации
+    pub interpreter: PathBuf,
+
+    /// Интерпретатор открыт ядром при регистрации (флаг `F`) и не должен
+    /// присутствовать внутри chroot
+    pub fix_binary: bool,
+}
+
+impl Qemu {
+    /// Имя эмулятора для разрядности целевой системы
+    pub fn name(bits: &Bits) -> &'static str {
+        match bits {
+            Bits::Arm32 => "qemu-arm",
+            Bits::Arm64 => "qemu-aarch64",
+        }
+    }
+
+    /// Может ли хост-система выполнять программы целевой системы без
+    /// эмуляции. 32-битные программы ARM выполняются на хосте aarch64
+    /// нативно, если процессор и ядро поддерживают AArch32.
+    pub fn is_native(bits: &Bits) -> bool {
+        match (bits, ARCH) {
+            (Bits::Arm32, "arm") | (Bits::Arm64, "aarch64") => true,
+            (Bits::Arm32, "aarch64") => supports_aarch32(),
+            _ => false,
+        }
+    }
+
+    /// Находит эмулятор для разрядности целевой системы. Возвращает `None`,
+    /// если эмуляция не нужна, и ошибку с описанием недостающих компонентов,
+    /// если эмулятор не может быть использован.
+    pub fn detect(bits: &Bits) -> Result<Option<Self>> {
+        if Self::is_native(bits) {
+            return Ok(None);
+        }
+
+        let name = Self::name(bits);
+
+        if !Path::new(BINFMT_DIR).join("status").exists() {
+            return Err(Error::msg(tr!(
+                "qemu.no_binfmt",
+                bits = bits,
+                name = name,
+                dir = BINFMT_DIR,
+                hint = tr!("qemu.hint")
+            )));
+        }
+
+        let reg = Path::new(BINFMT_DIR).join(name);
+        let contents = fs::read_to_string(&reg).map_err(|_| {
+            Error::msg(tr!(
+                "qemu.not_registered",
+                bits = bits,
+                name = name,
+                path = reg.display(),
+                hint = tr!("qemu.hint")
+            ))
+        })?;
+
+        if contents.lines().next() != Some("enabled") {
+            return Err(Error::msg(tr!("qemu.disabled", path = reg.display())));
+        }
+
+        let field = |key: &str| {
+            contents
+                .lines()
+                .find_map(|line| line.strip_prefix(key))
+                .map(str::trim)
+        };
+        let interpreter = field("interpreter ")
+            .map(PathBuf::from)
+            .ok_or(Error::msg(tr!("qemu.no_interpreter", path = reg.display())))?;
+        let fix_binary = field("flags:").is_some_and(|flags| flags.contains('F'));
+
+        if !fix_binary {
+            match is_static(&interpreter) {
+                Ok(true) => {}
+                Ok(false) => {
+                    return Err(Error::msg(tr!(
+                        "qemu.dynamic",
+                        path = interpreter.display(),
+                        hint = tr!("qemu.hint")
+                    )))
+                }
+                Err(why) => {
+                    return Err(Error::msg(tr!(
+                        "qemu.unusable",
+                        path = interpreter.display(),
+                        name = name,
+                        why = why,
+                        hint = tr!("qemu.hint")
+                    )))
+                }
+            }
+        }
+
+        Ok(Some(Self {
+            name: name.to_string(),
+            interpreter,
+            fix_binary,
+        }))
+    }
+}
+
+/// Поддерживает ли ядро aarch64 выполнение 32-битных программ: если
+/// процессор не поддерживает AArch32 (или ядро собрано без `COMPAT`), ядро
+/// не позволяет установить персональность `PER_LINUX32`
+fn supports_aarch32() -> bool {
+    // SAFETY: вызовы не затрагивают память процесса; исходная
+    // персональность восстанавливается
+    unsafe {
+        let current = libc::personality(PER_QUERY);
+        if current == -1 {
+            return false;
+        }
+        let supported = libc::personality(PER_LINUX32) != -1;
+        libc::personality(current as libc::c_ulong);
+
+        supported
+    }
+}
+
+/// Проверяет, что ELF-файл не требует динамического загрузчика. Читаются
+/// только заголовок файла и таблица заголовков программы.
+fn is_static<P: AsRef<Path>>(pth: P) -> Result<bool> {
+    let mut file = File::open(pth)?;
+    let mut ehdr = [0; EHDR_SIZE];
+    file.read_exact(&mut ehdr)
+        .map_err(|_| Error::msg(tr!("qemu.not_elf")))?;
+
+    if &ehdr[..4] != b"\x7fELF" {
+        return Err(Error::msg(tr!("qemu.not_elf")));
+    }
+    if ehdr[5] != 1 {
+        return Err(Error::msg(tr!("qemu.big_endian")));
+    }
+
+    let u16_at = |data: &[u8], off: usize| u16::from_le_bytes([data[off], data[off + 1]]) as usize;
+    let u32_at =
+        |data: &[u8], off: usize| u32::from_le_bytes(data[off..off + 4].try_into().unwrap());
+    let u64_at =
+        |data: &[u8], off: usize| u64::from_le_bytes(data[off..off + 8].try_into().unwrap());
+
+    // смещение таблицы заголовков программы, размер и число записей
+    let (phoff, phentsize, phnum) = match ehdr[4] {
+        1 => (
+            u32_at(&ehdr, 0x1c) as u64,
+            u16_at(&ehdr, 0x2a),
+            u16_at(&ehdr, 0x2c),
+        ),
+        2 => (
+            u64_at(&ehdr, 0x20),
+            u16_at(&ehdr, 0x36),
+            u16_at(&ehdr, 0x38),
+        ),
+        _ => return Err(Error::msg(tr!("qemu.elf_class"))),
+    };
+    if phnum > 0 && phentsize < 4 {
+        return Err(Error::msg(tr!("qemu.phdr_size")));
+    }
+
+    let mut phdrs = vec![0; phentsize * phnum];
+    file.seek(SeekFrom::Start(phoff))?;
+    file.read_exact(&mut phdrs)
+        .map_err(|_| Error::msg(tr!("qemu.truncated")))?;
+
+    Ok(phdrs
+        .chunks_exact(phentsize)
+        .all(|phdr| u32_at(phdr, 0) != PT_INTERP))
+}
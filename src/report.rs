@@ -0,0 +1,309 @@
+//! Build report generation (`alfa report`)
+//!
+//! Отчёт о последней сборке (`build_dir/last_build.toml`) для публикации в
+//! чате команды или в системе отслеживания ошибок: сведения о системе,
+//! параметры целевой системы, результаты сборки каждого пакета и сведения о
+//! хост-системе. Отчёт формируется в Markdown, HTML или JSON.
+
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::build_meta::{LastBuild, StepStatus};
+use crate::config::{Config, EnvDefault, System};
+use crate::host::{distro, kernel_version, HostRequirements};
+use crate::timing::format_duration;
+use crate::tr;
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Format {
+    #[value(alias = "md")]
+    Markdown,
+    Html,
+    Json,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Report<'a> {
+    pub alfa_version: &'static str,
+    pub system: &'a System,
+    pub target: &'a EnvDefault,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub board: Option<&'a str>,
+    pub build: BuildInfo,
+    pub packages: Vec<PackageInfo>,
+    pub host: HostInfo,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BuildInfo {
+    pub started: u64,
+    pub finished: Option<u64>,
+    pub ok: usize,
+    pub failed: usize,
+    pub pending: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PackageInfo {
+    pub stage: String,
+    pub name: String,
+    pub version: String,
+    pub url: Option<String>,
+    pub md5: Option<String>,
+    pub status: StepStatus,
+
+    /// Длительность сборки (в секундах)
+    pub duration: Option<f64>,
+    pub log: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HostInfo {
+    pub kernel: Option<String>,
+    pub distro: Option<String>,
+    pub tools: Vec<ToolInfo>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ToolInfo {
+    pub name: String,
+    pub version: Option<String>,
+}
+
+/// Раздел отчёта
+struct Table {
+    title: String,
+    header: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+impl<'a> Report<'a> {
+    pub fn new(config: &'a Config, last_build: &LastBuild, host: &HostRequirements) -> Self {
+        let pkgs = last_build
+            .step
+            .iter()
+            .map(|step| PackageInfo {
+                stage: step.stage.clone(),
+                name: step.name.clone(),
+                version: step.version.clone(),
+                url: step.download.clone(),
+                md5: step.md5.clone(),
+                status: step.status,
+                duration: step.duration,
+                log: step.log.clone(),
+            })
+            .collect::<Vec<_>>();
+        let count = |status| pkgs.iter().filter(|p| p.status == status).count();
+
+        Self {
+            alfa_version: env!("CARGO_PKG_VERSION"),
+            system: &config.system,
+            target: &config.env_default,
+            board: config.board.as_ref().map(|b| b.name.as_str()),
+            build: BuildInfo {
+                started: last_build.started,
+                finished: last_build.finished,
+                ok: count(StepStatus::Ok),
+                failed: count(StepStatus::Failed) + count(StepStatus::Interrupted),
+                pending: count(StepStatus::Pending),
+            },
+            packages: pkgs,
+            host: HostInfo {
+                kernel: kernel_version(),
+                distro: distro(),
+                tools: host
+                    .tool_versions()
+                    .into_iter()
+                    .map(|(name, version)| ToolInfo { name, version })
+                    .collect(),
+            },
+        }
+    }
+
+    pub fn render(&self, format: Format) -> Result<String> {
+        Ok(match format {
+            Format::Markdown => self.to_markdown(),
+            Format::Html => self.to_html(),
+            Format::Json => serde_json::to_string_pretty(self)? + "\n",
+        })
+    }
+
+    fn tables(&self) -> Vec<Table> {
+        let opt = |s: &Option<String>| s.clone().unwrap_or("-".to_string());
+
+        let mut target = vec![
+            vec![tr!("report.bits"), self.target.bits.to_string()],
+            vec![tr!("report.host"), self.target.lfa_host.clone()],
+            vec![tr!("report.target"), self.target.lfa_tgt.clone()],
+            vec![tr!("report.arch"), self.target.lfa_arch.clone()],
+            vec![tr!("report.float"), opt(&self.target.lfa_float)],
+            vec![tr!("report.fpu"), opt(&self.target.lfa_fpu)],
+        ];
+        if let Some(board) = self.board {
+            target.push(vec![tr!("report.board"), board.to_string()]);
+        }
+
+        let packages = self
+            .packages
+            .iter()
+            .map(|p| {
+                vec![
+                    format!("{}/{}", &p.stage, &p.name),
+                    p.version.clone(),
+                    p.status.to_string(),
+                    p.duration.map_or("-".to_string(), format_duration),
+                    opt(&p.url),
+                    opt(&p.md5),
+                    p.log.clone(),
+                ]
+            })
+            .collect();
+
+        let mut host = vec![
+            vec![tr!("report.kernel"), opt(&self.host.kernel)],
+            vec![tr!("report.distro"), opt(&self.host.distro)],
+        ];
+        for tool in &self.host.tools {
+            host.push(vec![
+                tool.name.clone(),
+                tool.version.clone().unwrap_or(tr!("host.not_found")),
+            ]);
+        }
+
+        vec![
+            Table {
+                title: tr!("report.target_title"),
+                header: vec![tr!("report.parameter"), tr!("report.value")],
+                rows: target,
+            },
+            Table {
+                title: tr!("report.packages_title"),
+                header: vec![
+                    tr!("report.package"),
+                    tr!("report.version"),
+                    tr!("report.status"),
+                    tr!("report.duration"),
+                    tr!("report.source"),
+                    "MD5".to_string(),
+                    tr!("report.log"),
+                ],
+                rows: packages,
+            },
+            Table {
+                title: tr!("report.host_title"),
+                header: vec![tr!("report.component"), tr!("report.version")],
+                rows: host,
+            },
+        ]
+    }
+
+    fn summary(&self) -> String {
+        tr!(
+            "report.summary",
+            total = self.packages.len(),
+            ok = self.build.ok,
+            failed = self.build.failed,
+            pending = self.build.pending
+        )
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut md = format!(
+            "# {}\n\n- {}: {}\n- {}: {}\n- {}: {}\n",
+            tr!(
+                "report.title",
+                name = &self.system.name,
+                version = &self.system.version
+            ),
+            tr!("report.author"),
+            &self.system.author,
+            tr!("report.alfa_version"),
+            self.alfa_version,
+            tr!("report.result"),
+            self.summary()
+        );
+
+        for Table {
+            title,
+            header,
+            rows,
+        } in self.tables()
+        {
+            md = format!(
+                "{md}\n## {title}\n\n| {} |\n|{}\n",
+                header.join(" | "),
+                " --- |".repeat(header.len())
+            );
+            for row in rows {
+                let row = row
+                    .iter()
+                    .map(|c| c.replace('|', "\\|"))
+                    .collect::<Vec<_>>();
+                md = format!("{md}| {} |\n", row.join(" | "));
+            }
+        }
+
+        md
+    }
+
+    pub fn to_html(&self) -> String {
+        let title = html_escape(&tr!(
+            "report.title",
+            name = &self.system.name,
+            version = &self.system.version
+        ));
+        let mut html = format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+            <title>{title}</title>\n<style>\n\
+            body {{ font-family: sans-serif; margin: 2em; }}\n\
+            table {{ border-collapse: collapse; margin-bottom: 1em; }}\n\
+            th, td {{ border: 1px solid #ccc; padding: 4px 8px; text-align: left; }}\n\
+            th {{ background: #eee; }}\n\
+            .ok {{ color: #080; }} .failed, .interrupted {{ color: #c00; }} .pending {{ color: #888; }}\n\
+            </style>\n</head>\n<body>\n<h1>{title}</h1>\n<ul>\n\
+            <li>{}: {}</li>\n<li>{}: {}</li>\n<li>{}: {}</li>\n</ul>\n",
+            tr!("report.author"),
+            html_escape(&self.system.author),
+            tr!("report.alfa_version"),
+            self.alfa_version,
+            tr!("report.result"),
+            self.summary()
+        );
+
+        for Table {
+            title,
+            header,
+            rows,
+        } in self.tables()
+        {
+            html = format!("{html}<h2>{title}</h2>\n<table>\n<tr>");
+            for h in header {
+                html = format!("{html}<th>{}</th>", html_escape(&h));
+            }
+            html.push_str("</tr>\n");
+            for row in rows {
+                html.push_str("<tr>");
+                for cell in row {
+                    let class = match cell.as_str() {
+                        "ok" | "failed" | "interrupted" | "pending" => format!(" class=\"{cell}\""),
+                        _ => String::new(),
+                    };
+                    html = format!("{html}<td{class}>{}</td>", html_escape(&cell));
+                }
+                html.push_str("</tr>\n");
+            }
+            html.push_str("</table>\n");
+        }
+
+        html + "</body>\n</html>\n"
+    }
+}
+
+fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
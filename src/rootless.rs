@@ -0,0 +1,93 @@
+//! Rootless build mode (user and mount namespaces)
+//!
+//! В этом режиме ALFA не требует прав суперпользователя: текущий процесс
+//! переходит в новые пространства имён пользователей и монтирования, где
+//! вызвавший пользователь отображается на `root`. Дерево сборки хранится в
+//! `$XDG_DATA_HOME/alfa/<имя build_dir>` (или `~/.local/share/alfa/...`) и
+//! монтируется (bind mount) по пути `build_dir` из `.profile.toml`, поэтому
+//! сборочные скрипты видят те же пути, что и при обычной сборке. Системная
+//! учётная запись сборочного пользователя не создаётся.
+
+use anyhow::{Error, Result};
+use std::{
+    env, fs,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+};
+
+use crate::error::AlfaError;
+use crate::mounts::mount;
+use crate::profile::Profile;
+use crate::tr;
+
+/// Директория, в которой хранится дерево сборки в режиме rootless
+pub fn storage_dir(profile: &Profile) -> Result<PathBuf> {
+    let base = match env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME").ok_or(Error::msg(tr!("rootless.no_home")))?)
+            .join(".local")
+            .join("share"),
+    };
+    let name = Path::new(&profile.build_dir)
+        .file_name()
+        .ok_or(Error::msg(tr!(
+            "rootless.bad_build_dir",
+            path = &profile.build_dir
+        )))?;
+
+    Ok(base.join("alfa").join(name))
+}
+
+/// Переводит текущий процесс в новые пространства имён пользователей и
+/// монтирования и монтирует дерево сборки по пути `build_dir`.
+///
+/// Должна вызываться до создания дополнительных потоков: ядро не позволяет
+/// многопоточному процессу создать пространство имён пользователей.
+pub fn enter(profile: &Profile) -> Result<PathBuf> {
+    let storage = storage_dir(profile)?;
+    fs::create_dir_all(&storage)?;
+
+    // SAFETY: функции не принимают указателей и всегда завершаются успешно
+    let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+
+    // SAFETY: вызов не затрагивает память процесса
+    if unsafe { libc::unshare(libc::CLONE_NEWUSER | libc::CLONE_NEWNS) } != 0 {
+        return Err(AlfaError::Privilege(tr!(
+            "rootless.unshare_failed",
+            why = std::io::Error::last_os_error()
+        ))
+        .into());
+    }
+
+    // без запрета `setgroups` непривилегированный процесс не может
+    // записать `gid_map`
+    fs::write("/proc/self/setgroups", "deny")?;
+    fs::write("/proc/self/uid_map", format!("0 {uid} 1"))?;
+    fs::write("/proc/self/gid_map", format!("0 {gid} 1"))?;
+
+    // изменения точек монтирования не должны распространяться наружу
+    mount(None::<&str>, "/", None, libc::MS_REC | libc::MS_PRIVATE)?;
+
+    let build_dir = Path::new(&profile.build_dir);
+    if !build_dir.is_dir() {
+        // создать `build_dir` в `/mnt` без прав root нельзя, поэтому поверх
+        // родительской директории монтируется tmpfs
+        let parent = build_dir
+            .parent()
+            .filter(|p| p.is_dir())
+            .ok_or(Error::msg(tr!(
+                "rootless.no_parent",
+                path = build_dir.display()
+            )))?;
+        mount(Some("tmpfs"), parent, Some("tmpfs"), 0)?;
+        fs::create_dir(build_dir)?;
+    }
+    mount(
+        Some(storage.as_os_str().as_bytes()),
+        build_dir,
+        None,
+        libc::MS_BIND | libc::MS_REC,
+    )?;
+
+    Ok(storage)
+}
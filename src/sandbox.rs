@@ -0,0 +1,291 @@
+//! Sandboxed execution of build scripts (`alfa-runner`)
+//!
+//! Сборочные скрипты запускаются через `alfa-runner`, который перед запуском
+//! скрипта:
+//!
+//! 1. создаёт новое сетевое пространство имён, в котором есть только
+//!    интерфейс `lo` (если инструкция не запросила доступ к сети);
+//! 2. создаёт новое пространство имён монтирования, в котором вся файловая
+//!    система, кроме дерева сборки и `/dev`, доступна только для чтения;
+//! 3. монтирует отдельный `/tmp` (tmpfs);
+//! 4. создаёт новое пространство имён PID: команда выполняется в дочернем
+//!    процессе с PID 1 и собственным `/proc`, а `alfa-runner` дожидается
+//!    её завершения;
+//! 5. при необходимости переходит к сборочному пользователю (стадии host
+//!    при сборке от имени root).
+//!
+//! Таким образом, `configure`, пытающийся что-то загрузить, завершается с
+//! ошибкой, `make install` не может записать файлы за пределами
+//! `build_dir`, а скрипт не видит процессы хоста. При завершении процесса
+//! с PID 1 ядро завершает все процессы пространства имён, поэтому
+//! запущенные скриптом демоны не переживают этап сборки.
+
+use anyhow::{Error, Result};
+use std::{
+    ffi::CString,
+    fs,
+    mem::{self, MaybeUninit},
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    process,
+};
+
+use crate::mounts::{mount, mounted_under, unescape};
+use crate::tr;
+
+/// Точки монтирования, которые остаются доступными для записи
+const WRITABLE: &[&str] = &["/dev"];
+
+/// Флаги точки монтирования, которые необходимо сохранить при перемонтировании
+/// (иначе ядро запрещает перемонтирование в пространстве имён пользователей)
+const KEEP_FLAGS: libc::c_ulong = libc::MS_NOSUID
+    | libc::MS_NODEV
+    | libc::MS_NOEXEC
+    | libc::MS_NOATIME
+    | libc::MS_NODIRATIME
+    | libc::MS_RELATIME;
+
+#[derive(Debug)]
+pub struct Sandbox {
+    /// Дерево сборки, доступное для записи
+    pub build_dir: PathBuf,
+
+    /// Оставить доступ к сети хост-системы
+    pub network: bool,
+
+    /// Пользователь, от имени которого выполняется команда (по умолчанию -
+    /// текущий)
+    pub user: Option<String>,
+}
+
+impl Sandbox {
+    /// Переводит текущий процесс в песочницу. Должна вызываться в
+    /// однопоточном процессе непосредственно перед запуском скрипта.
+    ///
+    /// Возвращает управление только в дочернем процессе (PID 1 нового
+    /// пространства имён PID), который должен выполнить команду. Исходный
+    /// процесс дожидается его завершения и завершается с тем же кодом.
+    pub fn enter(&self) -> Result<()> {
+        let build_dir = fs::canonicalize(&self.build_dir)?;
+
+        let mut flags = libc::CLONE_NEWNS | libc::CLONE_NEWPID;
+        if !self.network {
+            flags |= libc::CLONE_NEWNET;
+        }
+        // SAFETY: вызов не затрагивает память процесса
+        if unsafe { libc::unshare(flags) } != 0 {
+            return Err(Error::msg(tr!(
+                "sandbox.unshare_failed",
+                why = std::io::Error::last_os_error()
+            )));
+        }
+
+        if !self.network {
+            loopback_up()?;
+        }
+
+        mount(None::<&str>, "/", None, libc::MS_REC | libc::MS_PRIVATE)?;
+        // дерево сборки становится отдельной точкой монтирования, которая
+        // не затрагивается перемонтированием родительской файловой системы
+        mount(
+            Some(build_dir.as_os_str().as_bytes()),
+            &build_dir,
+            None,
+            libc::MS_BIND | libc::MS_REC,
+        )?;
+
+        for target in mounted_under("/")?.into_iter().rev() {
+            let writable = target.starts_with(&build_dir)
+                || WRITABLE.iter().any(|dir| target.starts_with(dir));
+            // точки монтирования, перекрытые другими, недоступны
+            if writable || !target.exists() {
+                continue;
+            }
+
+            if let Err(why) = remount_ro(&target) {
+                eprintln!("alfa-runner: {}: {why}", tr!("tui.warning"));
+            }
+        }
+        // `mounted_under` не возвращает сам корень
+        remount_ro("/")?;
+
+        // если дерево сборки находится в `/tmp`, то tmpfs его перекроет
+        if !build_dir.starts_with("/tmp") {
+            mount(Some("tmpfs"), "/tmp", Some("tmpfs"), libc::MS_NOSUID)?;
+        }
+
+        // первый дочерний процесс после `unshare(CLONE_NEWPID)` получает
+        // PID 1 в новом пространстве имён
+        // SAFETY: процесс однопоточный (см. выше)
+        match unsafe { libc::fork() } {
+            -1 => {
+                return Err(Error::msg(tr!(
+                    "sandbox.fork_failed",
+                    why = std::io::Error::last_os_error()
+                )))
+            }
+            0 => {}
+            pid => process::exit(wait_init(pid)),
+        }
+
+        mount_proc(&build_dir)?;
+
+        // пространства имён создаются с правами root, поэтому пользователь
+        // меняется последним
+        if let Some(user) = &self.user {
+            switch_user(user)?;
+        }
+        // смена пользователя сбрасывает этот сигнал, поэтому он задаётся
+        // после неё: песочница не должна пережить `alfa-runner`
+        // SAFETY: вызов не затрагивает память процесса
+        unsafe { libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL) };
+
+        Ok(())
+    }
+}
+
+/// Дожидается завершения процесса `pid` (PID 1 песочницы) и возвращает код
+/// для завершения `alfa-runner` (`128 + N`, если процесс завершён сигналом
+/// `N`, как в bash)
+fn wait_init(pid: libc::pid_t) -> i32 {
+    // `alfa` завершает сборку сигналами всей группе процессов; исходный
+    // процесс должен дождаться завершения песочницы, а не завершиться
+    // раньше неё
+    // SAFETY: обработчики не устанавливаются, сигналы игнорируются
+    unsafe {
+        libc::signal(libc::SIGINT, libc::SIG_IGN);
+        libc::signal(libc::SIGTERM, libc::SIG_IGN);
+    }
+
+    let mut status = 0;
+    loop {
+        // SAFETY: `status` - корректный указатель
+        if unsafe { libc::waitpid(pid, &mut status, 0) } == pid {
+            break;
+        }
+        if std::io::Error::last_os_error().kind() != std::io::ErrorKind::Interrupted {
+            return 1;
+        }
+    }
+
+    match libc::WIFSIGNALED(status) {
+        true => 128 + libc::WTERMSIG(status),
+        false => libc::WEXITSTATUS(status),
+    }
+}
+
+/// Монтирует `/proc` нового пространства имён PID поверх всех точек
+/// монтирования `proc` (в т.ч. `/proc` внутри chroot). Вне дерева сборки
+/// `/proc` остаётся доступным только для чтения.
+fn mount_proc(build_dir: &Path) -> Result<()> {
+    let mounts = fs::read_to_string("/proc/self/mounts")?;
+    let targets = mounts
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>())
+        .filter(|fields| fields.len() > 2 && fields[2] == "proc")
+        .map(|fields| PathBuf::from(unescape(fields[1])))
+        .collect::<Vec<_>>();
+
+    for target in targets {
+        let mut flags = libc::MS_NOSUID | libc::MS_NODEV | libc::MS_NOEXEC;
+        if !target.starts_with(build_dir) {
+            flags |= libc::MS_RDONLY;
+        }
+        mount(Some("proc"), &target, Some("proc"), flags)?;
+    }
+
+    Ok(())
+}
+
+/// Устанавливает группы, GID и UID пользователя `name`
+fn switch_user(name: &str) -> Result<()> {
+    let cname = CString::new(name)?;
+    // SAFETY: `cname` - корректная C-строка; поля записи копируются до
+    // следующего вызова `getpwnam`
+    let pw = unsafe { libc::getpwnam(cname.as_ptr()) };
+    if pw.is_null() {
+        return Err(Error::msg(tr!("sandbox.no_user", name = name)));
+    }
+    let (uid, gid) = unsafe { ((*pw).pw_uid, (*pw).pw_gid) };
+
+    // SAFETY: вызовы не затрагивают память процесса; GID меняется до UID,
+    // т.к. после смены UID права на это теряются
+    let rslt = unsafe {
+        libc::initgroups(cname.as_ptr(), gid) == 0
+            && libc::setgid(gid) == 0
+            && libc::setuid(uid) == 0
+    };
+    if !rslt {
+        return Err(Error::msg(tr!(
+            "sandbox.switch_user_failed",
+            name = name,
+            why = std::io::Error::last_os_error()
+        )));
+    }
+
+    Ok(())
+}
+
+fn remount_ro<P: AsRef<Path>>(target: P) -> Result<()> {
+    let target = target.as_ref();
+    let ctarget = CString::new(target.as_os_str().as_bytes())?;
+
+    let mut stat = MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: `ctarget` - корректная C-строка, `stat` инициализируется при
+    // успешном завершении
+    if unsafe { libc::statvfs(ctarget.as_ptr(), stat.as_mut_ptr()) } != 0 {
+        return Err(Error::msg(tr!(
+            "sandbox.stat_failed",
+            path = target.display(),
+            why = std::io::Error::last_os_error()
+        )));
+    }
+    // флаги `ST_*` совпадают с соответствующими флагами `MS_*`
+    let keep = unsafe { stat.assume_init() }.f_flag & KEEP_FLAGS;
+
+    mount(
+        None::<&str>,
+        target,
+        None,
+        libc::MS_BIND | libc::MS_REMOUNT | libc::MS_RDONLY | keep,
+    )
+    .map_err(|why| Error::msg(tr!("sandbox.read_only", why = why)))
+}
+
+/// Включает интерфейс `lo` в новом сетевом пространстве имён
+fn loopback_up() -> Result<()> {
+    let err = |what: String| {
+        Error::msg(tr!(
+            "sandbox.loopback_failed",
+            what = what,
+            why = std::io::Error::last_os_error()
+        ))
+    };
+
+    // SAFETY: все структуры инициализированы, дескриптор закрывается
+    unsafe {
+        let sock = libc::socket(libc::AF_INET, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, 0);
+        if sock < 0 {
+            return Err(err(tr!("sandbox.loopback_open")));
+        }
+
+        let mut ifr: libc::ifreq = mem::zeroed();
+        for (dst, src) in ifr.ifr_name.iter_mut().zip(b"lo") {
+            *dst = *src as libc::c_char;
+        }
+
+        let rslt = if libc::ioctl(sock, libc::SIOCGIFFLAGS, &mut ifr) != 0 {
+            Err(err(tr!("sandbox.loopback_query")))
+        } else {
+            ifr.ifr_ifru.ifru_flags |= libc::IFF_UP as libc::c_short;
+            if libc::ioctl(sock, libc::SIOCSIFFLAGS, &ifr) != 0 {
+                Err(err(tr!("sandbox.loopback_enable")))
+            } else {
+                Ok(())
+            }
+        };
+        libc::close(sock);
+
+        rslt
+    }
+}
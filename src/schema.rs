@@ -0,0 +1,194 @@
+//! Schema versions of `.config.toml` and `.profile.toml` and migrations
+//! between them
+//!
+//! Каждый файл содержит поле `schema_version`. Файлы без него считаются
+//! файлами версии 0. При чтении устаревшего файла ALFA последовательно
+//! применяет к нему миграции; если они изменили содержимое файла, ALFA
+//! сохраняет резервную копию исходного файла и перезаписывает его. Версии,
+//! для которых миграция не нужна (e.g. версия 1, в которой появилось само
+//! поле `schema_version`), миграции не имеют.
+
+use anyhow::Result;
+use colored::Colorize;
+use serde::de::DeserializeOwned;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use toml::{self, Value};
+
+use crate::error::AlfaError;
+use crate::events::{emit, is_human, Event};
+use crate::logging::{self, console, Level};
+use crate::prepare::user_exists;
+use crate::profile::USER_NAME_MAX;
+use crate::tr;
+
+/// Текущая версия схемы `.config.toml`
+pub const CONFIG_VERSION: i64 = 1;
+
+/// Текущая версия схемы `.profile.toml`
+pub const PROFILE_VERSION: i64 = 2;
+
+/// Миграция файла с версии `N - 1` на версию `N`
+type Migration = fn(&mut Value) -> Result<()>;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Schema {
+    Config,
+    Profile,
+}
+
+impl Schema {
+    pub fn version(&self) -> i64 {
+        match self {
+            Self::Config => CONFIG_VERSION,
+            Self::Profile => PROFILE_VERSION,
+        }
+    }
+
+    /// Миграции в порядке возрастания версий: `(N, миграция)` переводит
+    /// файл с версии `N - 1` на версию `N`
+    fn migrations(&self) -> &'static [(i64, Migration)] {
+        match self {
+            Self::Config => &[],
+            Self::Profile => &[(2, profile_v1_to_v2)],
+        }
+    }
+}
+
+/// Версия 2: имя сборочного пользователя не длиннее
+/// [`USER_NAME_MAX`](crate::profile::USER_NAME_MAX) символов (ограничение
+/// `useradd`); более длинные имена обрезаются. Имя пользователя, который
+/// уже создан в системе, сохраняется, иначе он остался бы без профиля, а
+/// `alfa prepare` создал бы ещё одного.
+fn profile_v1_to_v2(value: &mut Value) -> Result<()> {
+    if let Some(Value::String(name)) = value.get_mut("user_name") {
+        if name.len() > USER_NAME_MAX && !user_exists(name)? {
+            name.truncate(USER_NAME_MAX);
+        }
+    }
+
+    Ok(())
+}
+
+/// Возвращает версию схемы файла (0, если поле `schema_version` отсутствует)
+pub fn version_of(value: &Value) -> Result<i64> {
+    match value.get("schema_version") {
+        None => Ok(0),
+        Some(Value::Integer(ver)) if *ver >= 0 => Ok(*ver),
+        Some(ver) => Err(AlfaError::Config(tr!("schema.bad_version", version = ver)).into()),
+    }
+}
+
+/// Обновляет `value` до текущей версии схемы. Возвращает исходную версию.
+pub fn migrate(schema: Schema, value: &mut Value) -> Result<i64> {
+    let from = version_of(value)?;
+    let current = schema.version();
+
+    if from > current {
+        return Err(AlfaError::Config(tr!(
+            "schema.too_new",
+            from = from,
+            alfa = env!("CARGO_PKG_VERSION"),
+            current = current
+        ))
+        .into());
+    }
+
+    for (_, migration) in schema
+        .migrations()
+        .iter()
+        .filter(|(to, _)| from < *to && *to <= current)
+    {
+        migration(value)?;
+    }
+
+    if let Value::Table(table) = value {
+        table.insert("schema_version".to_string(), Value::Integer(current));
+    }
+
+    Ok(from)
+}
+
+/// Читает файл, при необходимости обновляя его до текущей версии схемы.
+/// Перед перезаписью сохраняет резервную копию `<file>.v<N>.bak`.
+pub fn read<T, P>(schema: Schema, pth: P) -> Result<T>
+where
+    T: DeserializeOwned,
+    P: AsRef<Path>,
+{
+    let pth = pth.as_ref();
+    read_value(schema, pth, true)?
+        .try_into()
+        .map_err(|why| AlfaError::Config(format!("{}: {why}", pth.display())).into())
+}
+
+/// Читает файл и обновляет его содержимое до текущей версии схемы. Если
+/// `upgrade = true` и миграции изменили содержимое файла, то обновлённый
+/// файл записывается на диск (с резервной копией `<file>.v<N>.bak`), иначе
+/// обновление выполняется только в памяти.
+pub fn read_value<P: AsRef<Path>>(schema: Schema, pth: P, upgrade: bool) -> Result<Value> {
+    let pth = pth.as_ref();
+    if !pth.exists() {
+        return Err(AlfaError::Config(tr!("schema.not_found", path = pth.display())).into());
+    }
+    let contents = fs::read_to_string(pth)?;
+    let mut value: Value = toml::from_str(&contents)
+        .map_err(|why| AlfaError::Config(format!("{}: {why}", pth.display())))?;
+
+    let original = value.clone();
+    let from = migrate(schema, &mut value)
+        .map_err(|why| AlfaError::Config(format!("{}: {why}", pth.display())))?;
+
+    if upgrade && from != schema.version() && !same_content(&original, &value) {
+        let bak = backup_path(pth, from);
+        fs::copy(pth, &bak)?;
+        fs::write(pth, toml::to_string(&value)?)?;
+
+        let message = tr!(
+            "schema.upgraded",
+            path = pth.display().to_string().dimmed(),
+            from = from,
+            to = schema.version(),
+            backup = bak.display().to_string().dimmed()
+        );
+        logging::info(&message);
+        match is_human() {
+            true if console(Level::Info) => println!("{message}"),
+            true => {}
+            false => emit(&Event::Info { message }),
+        }
+    }
+
+    Ok(value)
+}
+
+/// Совпадает ли содержимое файлов без учёта поля `schema_version`
+fn same_content(a: &Value, b: &Value) -> bool {
+    let strip = |v: &Value| {
+        let mut v = v.clone();
+        if let Value::Table(table) = &mut v {
+            table.remove("schema_version");
+        }
+        v
+    };
+    strip(a) == strip(b)
+}
+
+/// Свободное имя резервной копии: `<file>.v<N>.bak`, если такой копии ещё
+/// нет, иначе `<file>.v<N>.1.bak`, `<file>.v<N>.2.bak` и т.д. (прежние копии
+/// не перезаписываются)
+fn backup_path(pth: &Path, from: i64) -> PathBuf {
+    (0..)
+        .map(|i| {
+            let mut bak = pth.as_os_str().to_owned();
+            match i {
+                0 => bak.push(format!(".v{from}.bak")),
+                i => bak.push(format!(".v{from}.{i}.bak")),
+            }
+            PathBuf::from(bak)
+        })
+        .find(|bak| !bak.exists())
+        .unwrap()
+}
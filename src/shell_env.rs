@@ -0,0 +1,129 @@
+//! Clean shell environment of the build user (`.bash_profile`, `.bashrc`)
+//!
+//! По аналогии с LFS сборка выполняется в «чистом» окружении: `.bash_profile`
+//! заменяет login shell новым экземпляром bash с пустым окружением (кроме
+//! `HOME`, `TERM` и `PS1`), а `.bashrc` задаёт только необходимые
+//! переменные. `alfa build` запускает сборочные скрипты с тем же набором
+//! переменных, поэтому отладочная оболочка сборочного пользователя
+//! (`su - <user>`) ведёт себя так же, как и сборка.
+
+use anyhow::Result;
+use colored::Colorize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use crate::config::Config;
+use crate::instruction::export;
+use crate::process_msg;
+use crate::profile::Profile;
+use crate::tr;
+use crate::tui::process_msg_result_err;
+
+const PATH: &str = "/usr/bin:/bin";
+
+#[derive(Debug)]
+pub struct ShellEnv {
+    /// Домашняя директория сборочного пользователя
+    pub home: PathBuf,
+
+    /// Переменные, экспортируемые в `.bashrc`
+    pub vars: BTreeMap<String, String>,
+}
+
+impl ShellEnv {
+    pub fn new<P: AsRef<Path>>(config: &Config, profile: &Profile, home: P) -> Self {
+        let mut vars = BTreeMap::new();
+        let lfa = format!("{}/lfa", &profile.build_dir);
+        let cross = format!("{lfa}/cross-tools");
+
+        vars.insert("LC_ALL".to_string(), "POSIX".to_string());
+        vars.insert("PATH".to_string(), format!("{cross}/bin:{PATH}"));
+        vars.insert("LFA".to_string(), lfa);
+        vars.insert("LFA_CROSS".to_string(), cross);
+        for (k, v) in profile.to_env_map() {
+            vars.insert(k.to_string(), v);
+        }
+        // `env_default`, `board` и `env` из `.config.toml`
+        vars.extend(config.to_env_map());
+
+        Self {
+            home: home.as_ref().to_path_buf(),
+            vars,
+        }
+    }
+
+    fn header() -> String {
+        format!(
+            "# WARNING: autogenerated by ALFA (ver. {}). Do not edit.\n\
+            # Changes will be lost on the next `alfa prepare` or `alfa build`.\n\n",
+            env!("CARGO_PKG_VERSION")
+        )
+    }
+
+    pub fn bash_profile(&self) -> String {
+        format!(
+            "{}exec env -i HOME=$HOME TERM=$TERM PS1='\\u:\\w\\$ ' /bin/bash\n",
+            Self::header()
+        )
+    }
+
+    pub fn bashrc(&self) -> Result<String> {
+        let mut sh = format!("{}set +h\numask 022\n\n", Self::header());
+        for (k, v) in &self.vars {
+            sh.push_str(&export(k, v)?);
+        }
+        Ok(sh)
+    }
+
+    /// Записывает `.bash_profile` и `.bashrc` в домашнюю директорию
+    /// пользователя. Возвращает пути до изменённых файлов (файлы, содержимое
+    /// которых не изменилось, не перезаписываются).
+    pub fn write(&self) -> Result<Vec<PathBuf>> {
+        let mut changed = Vec::new();
+
+        for (name, contents) in [
+            (".bash_profile", self.bash_profile()),
+            (".bashrc", self.bashrc()?),
+        ] {
+            let pth = self.home.join(name);
+            if fs::read_to_string(&pth).is_ok_and(|old| old == contents) {
+                continue;
+            }
+
+            process_msg!(tr!(
+                "shell_env.write",
+                path = pth.display().to_string().dimmed()
+            ));
+            let rslt = fs::write(&pth, contents);
+            process_msg_result_err(rslt.is_ok(), rslt.as_ref().err());
+            rslt?;
+            changed.push(pth);
+        }
+
+        Ok(changed)
+    }
+
+    /// Окружение сборочного скрипта: то же, что в интерактивной оболочке
+    /// пользователя после `.bash_profile` и `.bashrc`. Неинтерактивный bash
+    /// выполняет `.bashrc` через `BASH_ENV` (`set +h`, `umask 022`).
+    pub fn to_env_map(&self) -> HashMap<String, String> {
+        let mut map = HashMap::new();
+        map.insert("HOME".to_string(), self.home.display().to_string());
+        map.insert(
+            "BASH_ENV".to_string(),
+            self.home.join(".bashrc").display().to_string(),
+        );
+        map.insert(
+            "TERM".to_string(),
+            env::var("TERM").unwrap_or("dumb".to_string()),
+        );
+        for (k, v) in &self.vars {
+            map.insert(k.clone(), v.clone());
+        }
+
+        map
+    }
+}
@@ -0,0 +1,188 @@
+//! Disk space estimation and pre-flight space checks
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    os::unix::fs::MetadataExt,
+    path::Path,
+};
+use toml;
+
+use crate::build_meta::PackageList;
+use crate::host::{free_space, same_fs, Check};
+use crate::instruction::Instruction;
+use crate::mounts::mounted_under;
+use crate::tr;
+
+/// Оценка места, необходимого для сборки выбранных пакетов (в МиБ)
+#[derive(Debug, Default)]
+pub struct Estimate {
+    /// Суммарный размер архивов с исходным кодом
+    pub source: u64,
+
+    /// Суммарный объём места, занимаемого при сборке и установке пакетов
+    pub build: u64,
+
+    /// Пакеты, для которых не указаны `size_source` или `size_build`
+    pub unknown: Vec<String>,
+}
+
+impl Estimate {
+    /// Суммирует оценки для пакетов из порядка сборки. Архив каждого пакета
+    /// учитывается один раз, даже если пакет собирается несколько раз.
+    pub fn new<'a, I>(packages: &PackageList, instrs: I) -> Self
+    where
+        I: IntoIterator<Item = &'a Instruction>,
+    {
+        let mut est = Self::default();
+        let mut seen = HashSet::new();
+
+        for instr in instrs {
+            let Some((key, pkg)) = packages.find(instr) else {
+                continue;
+            };
+
+            if seen.insert(key.clone()) {
+                est.source += pkg.size_source.unwrap_or(0);
+            }
+            est.build += pkg.size_build.unwrap_or(0);
+
+            if (pkg.size_source.is_none() || pkg.size_build.is_none()) && !est.unknown.contains(key)
+            {
+                est.unknown.push(key.clone());
+            }
+        }
+
+        est
+    }
+
+    /// Сравнивает оценку со свободным местом в директории сборки и в кеше
+    /// архивов (если он расположен на другой файловой системе)
+    pub fn check<P: AsRef<Path>>(&self, build_dir: P, cache_dir: Option<&str>) -> Vec<Check> {
+        let build_dir = build_dir.as_ref();
+        let mut checks = Vec::new();
+
+        let mut required = self.source + self.build;
+        let cache = match cache_dir {
+            // архивы хранятся и в кеше, и в `build_dir/src`
+            Some(cache) if same_fs(build_dir, cache).unwrap_or(true) => {
+                required += self.source;
+                None
+            }
+            Some(cache) => Some(cache),
+            None => None,
+        };
+        checks.push(space_check(build_dir, required));
+
+        if let Some(cache) = cache {
+            checks.push(space_check(Path::new(cache), self.source));
+        }
+
+        checks
+    }
+}
+
+fn space_check(dir: &Path, required: u64) -> Check {
+    let free = free_space(dir).ok();
+    Check {
+        name: tr!("host.free_space", path = dir.display()),
+        required: format!(">= {required} MiB"),
+        ok: free.is_some_and(|free| free >= required),
+        found: free.map(|free| format!("{free} MiB")),
+    }
+}
+
+/// Фактически занятое пакетами место, измеренное во время сборки
+/// (`build_dir/usage.toml`). Формат совпадает с полями `size_source` и
+/// `size_build` из `packages.toml`, поэтому значения можно переносить туда
+/// для уточнения оценок.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Usage {
+    #[serde(default)]
+    pub package: BTreeMap<String, PackageUsage>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
+pub struct PackageUsage {
+    pub size_source: u64,
+    pub size_build: u64,
+}
+
+impl Usage {
+    pub fn read<P: AsRef<Path>>(pth: P) -> Result<Self> {
+        let contents = fs::read_to_string(&pth)?;
+        let data = toml::from_str(&contents)?;
+
+        Ok(data)
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, pth: P) -> Result<()> {
+        let contents = toml::to_string(&self)?;
+        fs::write(&pth, contents)?;
+
+        Ok(())
+    }
+
+    /// Записывает измерение для пакета `name` в файл `pth`. Если пакет
+    /// собирается несколько раз, сохраняется максимальное значение.
+    pub fn record<P: AsRef<Path>>(pth: P, name: &str, usage: PackageUsage) -> Result<()> {
+        let pth = pth.as_ref();
+        let mut data = if pth.exists() {
+            Self::read(pth)?
+        } else {
+            Self::default()
+        };
+
+        let entry = data.package.entry(name.to_string()).or_default();
+        entry.size_source = entry.size_source.max(usage.size_source);
+        entry.size_build = entry.size_build.max(usage.size_build);
+
+        data.write(pth)
+    }
+}
+
+/// Место (в байтах), занимаемое файлами в дереве `root`, аналогично `du`.
+/// Точки монтирования внутри дерева (в т.ч. привязанные к тому же дереву
+/// `src` и `scripts` в chroot) и другие файловые системы не учитываются,
+/// файлы с несколькими жёсткими ссылками учитываются один раз.
+pub fn tree_size<P: AsRef<Path>>(root: P) -> u64 {
+    let Ok(root) = fs::canonicalize(root) else {
+        return 0;
+    };
+    let Ok(dev) = fs::metadata(&root).map(|meta| meta.dev()) else {
+        return 0;
+    };
+    let skip = mounted_under(&root).unwrap_or_default();
+
+    let mut size = 0;
+    let mut seen = HashSet::new();
+    let mut dirs = vec![root];
+    while let Some(dir) = dirs.pop() {
+        // файлы могут удаляться сборочным скриптом во время обхода
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Ok(meta) = entry.metadata() else {
+                continue;
+            };
+            if meta.dev() != dev || skip.contains(&path) {
+                continue;
+            }
+            // запоминаются только файлы с несколькими жёсткими ссылками
+            if meta.nlink() > 1 && !meta.is_dir() && !seen.insert(meta.ino()) {
+                continue;
+            }
+
+            size += meta.blocks() * 512;
+            if meta.is_dir() {
+                dirs.push(path);
+            }
+        }
+    }
+
+    size
+}
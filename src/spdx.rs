@@ -0,0 +1,243 @@
+//! Checks of SPDX license expressions (`license` in `packages.toml`)
+//!
+//! Выражение разбирается по грамматике SPDX: идентификаторы лицензий
+//! (с необязательным `+`), операторы `AND`, `OR`, `WITH` и скобки.
+//! Идентификаторы сравниваются без учёта регистра со списком
+//! [`LICENSES`]; `LicenseRef-*` и `DocumentRef-*:LicenseRef-*` считаются
+//! известными. Полный список SPDX не встраивается, поэтому неизвестный
+//! идентификатор - повод для предупреждения, а не ошибка.
+
+use crate::build_meta::PackageList;
+use crate::tr;
+
+/// Идентификаторы лицензий SPDX, которые встречаются в пакетах LFA и
+/// в основных дистрибутивах Linux
+pub const LICENSES: &[&str] = &[
+    "0BSD",
+    "AFL-2.1",
+    "Apache-2.0",
+    "Artistic-1.0",
+    "Artistic-2.0",
+    "BSD-1-Clause",
+    "BSD-2-Clause",
+    "BSD-2-Clause-Patent",
+    "BSD-3-Clause",
+    "BSD-4-Clause",
+    "BSL-1.0",
+    "bzip2-1.0.6",
+    "CC0-1.0",
+    "CC-BY-4.0",
+    "CC-BY-SA-4.0",
+    "CDDL-1.0",
+    "curl",
+    "EPL-2.0",
+    "FSFAP",
+    "FSFUL",
+    "FSFULLR",
+    "FTL",
+    "GFDL-1.3-only",
+    "GFDL-1.3-or-later",
+    "GPL-1.0-or-later",
+    "GPL-2.0-only",
+    "GPL-2.0-or-later",
+    "GPL-3.0-only",
+    "GPL-3.0-or-later",
+    "HPND",
+    "ICU",
+    "IJG",
+    "ISC",
+    "LGPL-2.0-only",
+    "LGPL-2.0-or-later",
+    "LGPL-2.1-only",
+    "LGPL-2.1-or-later",
+    "LGPL-3.0-only",
+    "LGPL-3.0-or-later",
+    "Libpng",
+    "libpng-2.0",
+    "LPPL-1.3c",
+    "MIT",
+    "MIT-0",
+    "MPL-1.1",
+    "MPL-2.0",
+    "NCSA",
+    "OFL-1.1",
+    "OpenSSL",
+    "PHP-3.01",
+    "PSF-2.0",
+    "Python-2.0",
+    "Ruby",
+    "SGI-B-2.0",
+    "Sleepycat",
+    "TCL",
+    "Unicode-3.0",
+    "Unicode-DFS-2016",
+    "Unlicense",
+    "Vim",
+    "W3C",
+    "X11",
+    "Zlib",
+    "ZPL-2.1",
+];
+
+/// Исключения SPDX (правая часть `WITH`)
+pub const EXCEPTIONS: &[&str] = &[
+    "Autoconf-exception-2.0",
+    "Autoconf-exception-3.0",
+    "Bison-exception-2.2",
+    "Classpath-exception-2.0",
+    "GCC-exception-2.0",
+    "GCC-exception-3.1",
+    "Libtool-exception",
+    "Linux-syscall-note",
+    "LLVM-exception",
+    "OpenSSL-exception",
+    "u-boot-exception-2.0",
+];
+
+/// Проверяет выражение SPDX. Возвращает список проблем: синтаксическую
+/// ошибку или неизвестные идентификаторы лицензий и исключений.
+pub fn check(expr: &str) -> Vec<String> {
+    let tokens = tokenize(expr);
+    let mut parser = Parser {
+        tokens: &tokens,
+        pos: 0,
+        issues: Vec::new(),
+    };
+
+    let parsed = parser.or_expr();
+    if parsed.is_none() || parser.pos != tokens.len() {
+        return vec![tr!("spdx.malformed", expr = expr)];
+    }
+
+    parser.issues
+}
+
+/// Проверяет лицензии всех пакетов. Возвращает проблемы с именами пакетов.
+pub fn check_packages(packages: &PackageList) -> Vec<String> {
+    packages
+        .package
+        .iter()
+        .filter_map(|(name, pkg)| Some((name, pkg.license.as_deref()?)))
+        .flat_map(|(name, license)| {
+            check(license)
+                .into_iter()
+                .map(move |issue| tr!("spdx.package", name = name, issue = issue))
+        })
+        .collect()
+}
+
+/// Скобки и слова выражения
+fn tokenize(expr: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    for (i, c) in expr.char_indices() {
+        if c.is_whitespace() || c == '(' || c == ')' {
+            if let Some(s) = start.take() {
+                tokens.push(&expr[s..i]);
+            }
+            if !c.is_whitespace() {
+                tokens.push(&expr[i..i + 1]);
+            }
+        } else if start.is_none() {
+            start = Some(i);
+        }
+    }
+    if let Some(s) = start {
+        tokens.push(&expr[s..]);
+    }
+
+    tokens
+}
+
+/// Рекурсивный спуск: `or := and (OR and)*`, `and := with (AND with)*`,
+/// `with := atom (WITH exception)?`, `atom := license | '(' or ')'`
+struct Parser<'a> {
+    tokens: &'a [&'a str],
+    pos: usize,
+    issues: Vec<String>,
+}
+
+impl<'a> Parser<'a> {
+    fn next(&mut self) -> Option<&'a str> {
+        let token = self.tokens.get(self.pos).copied();
+        self.pos += 1;
+        token
+    }
+
+    fn accept(&mut self, op: &str) -> bool {
+        match self.tokens.get(self.pos) == Some(&op) {
+            true => {
+                self.pos += 1;
+                true
+            }
+            false => false,
+        }
+    }
+
+    fn or_expr(&mut self) -> Option<()> {
+        self.and_expr()?;
+        while self.accept("OR") {
+            self.and_expr()?;
+        }
+        Some(())
+    }
+
+    fn and_expr(&mut self) -> Option<()> {
+        self.with_expr()?;
+        while self.accept("AND") {
+            self.with_expr()?;
+        }
+        Some(())
+    }
+
+    fn with_expr(&mut self) -> Option<()> {
+        self.atom()?;
+        if self.accept("WITH") {
+            let exception = self.next().filter(|t| is_id(t))?;
+            if !contains(EXCEPTIONS, exception) {
+                self.issues
+                    .push(tr!("spdx.unknown_exception", id = exception));
+            }
+        }
+        Some(())
+    }
+
+    fn atom(&mut self) -> Option<()> {
+        if self.accept("(") {
+            self.or_expr()?;
+            return self.accept(")").then_some(());
+        }
+
+        let token = self.next()?;
+        let id = token.strip_suffix('+').unwrap_or(token);
+        if !is_id(id) {
+            return None;
+        }
+        if !is_license_ref(id) && !contains(LICENSES, id) {
+            self.issues.push(tr!("spdx.unknown_license", id = id));
+        }
+        Some(())
+    }
+}
+
+/// Идентификатор: буквы, цифры, `-` и `.` (и `:` в `DocumentRef-*`), но не
+/// оператор
+fn is_id(token: &str) -> bool {
+    !token.is_empty()
+        && !matches!(token, "AND" | "OR" | "WITH" | "(" | ")")
+        && token
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | ':'))
+}
+
+fn is_license_ref(id: &str) -> bool {
+    let id = match id.split_once(':') {
+        Some((doc, id)) if doc.starts_with("DocumentRef-") => id,
+        _ => id,
+    };
+    id.starts_with("LicenseRef-") && id.len() > "LicenseRef-".len() && !id.contains(':')
+}
+
+fn contains(list: &[&str], id: &str) -> bool {
+    list.iter().any(|known| known.eq_ignore_ascii_case(id))
+}
@@ -0,0 +1,221 @@
+//! Build time measurement in SBU and ETA prediction
+//!
+//! Как и в руководстве LFS, время сборки пакета выражается в SBU (Standard
+//! Build Unit) - отношении ко времени сборки эталонного пакета на этой же
+//! машине (по умолчанию `binutils-pass1`). Время сборки каждого пакета
+//! записывается в историю `$XDG_DATA_HOME/alfa/timing.toml` (или
+//! `~/.local/share/alfa/timing.toml`), по которой оценивается оставшееся
+//! время сборки.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    mem::MaybeUninit,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use toml;
+
+/// Эталонный пакет по умолчанию (1 SBU)
+pub const DEFAULT_SBU_REFERENCE: &str = "binutils-pass1";
+
+/// Сколько последних сборок каждого пакета хранится в истории
+const HISTORY_LEN: usize = 10;
+
+/// Файл истории времени сборки
+pub fn history_path() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?)
+            .join(".local")
+            .join("share"),
+    };
+
+    Some(base.join("alfa").join("timing.toml"))
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct History {
+    /// Сборки каждого пакета, от старых к новым
+    #[serde(default)]
+    pub package: BTreeMap<String, Vec<Record>>,
+}
+
+/// Время одной сборки пакета
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Record {
+    pub version: String,
+
+    /// Время окончания сборки (UNIX time)
+    pub finished: u64,
+
+    /// Реальное время сборки (в секундах)
+    pub wall: f64,
+
+    /// Процессорное время (user + system) всех процессов сборки (в секундах)
+    pub cpu: f64,
+
+    /// Время сборки в SBU, если время сборки эталонного пакета известно
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sbu: Option<f64>,
+}
+
+impl Record {
+    pub fn new(version: &str, wall: f64, cpu: f64) -> Self {
+        Self {
+            version: version.to_string(),
+            finished: unix_time(),
+            wall: round(wall, 3),
+            cpu: round(cpu, 3),
+            sbu: None,
+        }
+    }
+
+    /// Краткая сводка, e.g. `2m 05s, CPU 7m 40s, 1.3 SBU`
+    pub fn summary(&self) -> String {
+        let mut s = format!(
+            "{}, CPU {}",
+            format_duration(self.wall),
+            format_duration(self.cpu)
+        );
+        if let Some(sbu) = self.sbu {
+            s = format!("{s}, {sbu:.1} SBU");
+        }
+        s
+    }
+}
+
+impl History {
+    /// Читает историю; отсутствующий файл считается пустой историей
+    pub fn read<P: AsRef<Path>>(pth: P) -> Result<Self> {
+        if !pth.as_ref().exists() {
+            return Ok(Self::default());
+        }
+        let contents = fs::read_to_string(&pth)?;
+        let data = toml::from_str(&contents)?;
+
+        Ok(data)
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, pth: P) -> Result<()> {
+        if let Some(dir) = pth.as_ref().parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let contents = toml::to_string(&self)?;
+        fs::write(&pth, contents)?;
+
+        Ok(())
+    }
+
+    /// Последняя сборка пакета
+    pub fn last(&self, name: &str) -> Option<&Record> {
+        self.package.get(name).and_then(|recs| recs.last())
+    }
+
+    /// Длительность 1 SBU (в секундах) - время последней сборки эталонного
+    /// пакета
+    pub fn sbu(&self, reference: &str) -> Option<f64> {
+        self.last(reference)
+            .map(|rec| rec.wall)
+            .filter(|wall| *wall > 0.0)
+    }
+
+    /// Добавляет сборку в историю, вычисляя её время в SBU
+    pub fn push(&mut self, name: &str, mut rec: Record, reference: &str) -> Record {
+        rec.sbu = if name == reference {
+            Some(1.0)
+        } else {
+            self.sbu(reference).map(|sbu| round(rec.wall / sbu, 2))
+        };
+
+        let recs = self.package.entry(name.to_string()).or_default();
+        recs.push(rec.clone());
+        if recs.len() > HISTORY_LEN {
+            recs.drain(..recs.len() - HISTORY_LEN);
+        }
+
+        rec
+    }
+
+    /// Оценка времени сборки пакетов (в секундах) по их последним сборкам.
+    /// Также возвращает пакеты, которые ещё ни разу не собирались.
+    pub fn eta<'a, I>(&self, names: I) -> (f64, Vec<&'a str>)
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let mut total = 0.0;
+        let mut unknown = Vec::new();
+
+        for name in names {
+            match self.last(name) {
+                Some(rec) => total += rec.wall,
+                None => unknown.push(name),
+            }
+        }
+
+        (total, unknown)
+    }
+}
+
+/// Текущее время (UNIX time)
+pub fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// Форматирует UNIX time как местное время: `2024-12-31 23:59:59`
+pub fn format_time(secs: u64) -> String {
+    let time = secs as libc::time_t;
+    let mut tm = MaybeUninit::<libc::tm>::uninit();
+    // SAFETY: `tm` инициализируется при успешном завершении
+    if unsafe { libc::localtime_r(&time, tm.as_mut_ptr()) }.is_null() {
+        return secs.to_string();
+    }
+    let tm = unsafe { tm.assume_init() };
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        tm.tm_year + 1900,
+        tm.tm_mon + 1,
+        tm.tm_mday,
+        tm.tm_hour,
+        tm.tm_min,
+        tm.tm_sec
+    )
+}
+
+fn round(x: f64, digits: i32) -> f64 {
+    let k = 10f64.powi(digits);
+    (x * k).round() / k
+}
+
+/// Форматирует длительность: `1h 02m`, `3m 05s`, `12s`
+pub fn format_duration(secs: f64) -> String {
+    let secs = secs.round() as u64;
+    let (h, m, s) = (secs / 3600, secs / 60 % 60, secs % 60);
+
+    if h > 0 {
+        format!("{h}h {m:02}m")
+    } else if m > 0 {
+        format!("{m}m {s:02}s")
+    } else {
+        format!("{s}s")
+    }
+}
+
+/// Процессорное время (user + system) завершившихся дочерних процессов и
+/// их потомков (в секундах)
+pub fn children_cpu_time() -> f64 {
+    let mut usage = MaybeUninit::<libc::rusage>::uninit();
+    // SAFETY: `usage` инициализируется при успешном завершении
+    if unsafe { libc::getrusage(libc::RUSAGE_CHILDREN, usage.as_mut_ptr()) } != 0 {
+        return 0.0;
+    }
+    let usage = unsafe { usage.assume_init() };
+
+    let tv = |tv: libc::timeval| tv.tv_sec as f64 + tv.tv_usec as f64 / 1_000_000.0;
+    tv(usage.ru_utime) + tv(usage.ru_stime)
+}
@@ -1,13 +1,65 @@
 //! Functions for console I/O
 
-use anyhow::Result;
+use anyhow::{Error, Result};
 use colored::Colorize;
 use std::{
     fmt::Display,
-    io::{stdin, stdout, Write},
+    io::{stdin, stdout, IsTerminal, Write},
+    sync::{atomic::Ordering, Mutex, OnceLock},
 };
 
+use getch_rs::{enable_echo_input, Getch, Key};
+
+use crate::events::{emit, is_human, is_json, Event};
+use crate::logging::{self, console, Level};
+use crate::tr;
+
+static POLICY: OnceLock<Policy> = OnceLock::new();
+
+/// Текущая операция (см. [`process_msg!`](crate::process_msg))
+static PROCESS: Mutex<Option<String>> = Mutex::new(None);
+
+/// Как отвечать на вопросы пользователю
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Policy {
+    /// Спрашивать пользователя, если stdin - терминал
+    #[default]
+    Ask,
+
+    /// Отвечать "да" на все вопросы "да/нет" (`--yes`)
+    Yes,
+
+    /// Отвечать "нет" на все вопросы "да/нет" (`--no`)
+    No,
+
+    /// Не задавать вопросов: использовать ответы по умолчанию, а при их
+    /// отсутствии завершаться с ошибкой (`--non-interactive`)
+    NonInteractive,
+}
+
+pub fn set_policy(policy: Policy) {
+    let _ = POLICY.set(policy);
+}
+
+/// Действующая политика: без терминала на stdin и в режиме JSON вопросы
+/// не задаются
+pub fn policy() -> Policy {
+    match POLICY.get().copied().unwrap_or_default() {
+        Policy::Ask if is_json() || !stdin().is_terminal() => Policy::NonInteractive,
+        policy => policy,
+    }
+}
+
+/// Можно ли задавать вопросы пользователю
+pub fn is_interactive() -> bool {
+    policy() == Policy::Ask
+}
+
 pub fn answer<M: Display>(msg: M, def_val: Option<String>) -> Result<String> {
+    if !is_interactive() {
+        return def_val.ok_or(Error::msg(tr!("tui.answer_required", msg = msg)));
+    }
+
     let mut ans = String::new();
 
     print!("{}", &msg);
@@ -17,7 +69,9 @@ pub fn answer<M: Display>(msg: M, def_val: Option<String>) -> Result<String> {
     print!(" {}{} ", "::".bold(), ">".bold().magenta());
     stdout().flush()?;
 
-    stdin().read_line(&mut ans)?;
+    if stdin().read_line(&mut ans)? == 0 {
+        return Err(Error::msg(tr!("tui.unexpected_eof")));
+    }
     ans = ans.trim().to_string();
 
     if let Some(def_val) = def_val {
@@ -31,72 +85,180 @@ pub fn answer<M: Display>(msg: M, def_val: Option<String>) -> Result<String> {
     }
 }
 
+/// Задаёт вопрос с ответом "да/нет" (ответ по умолчанию - "нет"), см.
+/// [`yesno`]
 #[macro_export]
 macro_rules! yesno {
-    ($($arg:tt)*) => {{
-        use std::{
-            fmt::Display,
-            io::{stdout, Write},
-        };
-        use getch_rs::{Getch, Key, enable_echo_input};
-        use colored::Colorize;
-
-        let msg = format!("{}", format_args!($($arg)*));
-        print!(" :: {} {} ", msg.bold().magenta(), "[y/n]".dimmed());
-        let _ = stdout().flush();
-
-        enable_echo_input();
-        let g = Getch::new();
-
-        let rslt = match g.getch() {
-            Ok(Key::Char('y')) | Ok(Key::Char('Y')) => true,
-            _ => false,
-        };
-
-        println!();
+    ($fmt:literal $($arg:tt)*) => {
+        $crate::tui::yesno(format!($fmt $($arg)*), false)
+    };
 
-        rslt
-    }};
+    ($msg:expr) => {
+        $crate::tui::yesno($msg, false)
+    };
 }
 
+/// Заголовок этапа работы; записывается в журнал (см. [`crate::logging`])
 #[macro_export]
 macro_rules! msg {
     () => {
-        println!();
+        if $crate::logging::console($crate::logging::Level::Info) {
+            println!();
+        }
+    };
+
+    ($fmt:literal $($arg:tt)*) => {
+        $crate::msg!(format!($fmt $($arg)*))
     };
 
-    ($($arg:tt)*) => {{
+    ($msg:expr) => {{
         use colored::Colorize;
-        let msg = format!("{}", format_args!($($arg)*)).bold().yellow();
-        println!("\n==> {}", msg);
+        let msg = $msg.to_string();
+        $crate::logging::info(&msg);
+        if !$crate::events::is_human() {
+            $crate::events::emit(&$crate::events::Event::Stage { message: msg });
+        } else if $crate::logging::console($crate::logging::Level::Info) {
+            println!("\n==> {}", msg.bold().yellow());
+        }
     }};
 }
 
+/// Начало операции, результат которой выводится функциями
+/// `process_msg_result*`. В режиме JSON ничего не выводит: результат
+/// операции передаётся отдельным событием.
 #[macro_export]
 macro_rules! process_msg {
-    ($($arg:tt)*) => {{
-        use ::std::io::{Write, stdout};
+    ($fmt:literal $($arg:tt)*) => {
+        $crate::process_msg!(format!($fmt $($arg)*))
+    };
+
+    ($msg:expr) => {
+        $crate::tui::process_start($msg.to_string())
+    };
+}
 
-        print!("{}... ", format_args!($($arg)*));
+/// См. [`process_msg!`](crate::process_msg)
+pub fn process_start(msg: String) {
+    if console(Level::Info) {
+        print!("{msg}... ");
         let _ = stdout().flush();
-    }};
+        logging::LINE_OPEN.store(true, Ordering::SeqCst);
+    }
+    *PROCESS.lock().unwrap_or_else(|e| e.into_inner()) = Some(msg);
+}
+
+/// Записывает результат текущей операции в журнал. Если начало операции
+/// не было выведено (`-q`), а операция завершилась ошибкой, выводит его.
+fn process_finish(ok: bool, result: &str) {
+    let msg = PROCESS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .take()
+        .unwrap_or_default();
+    logging::LINE_OPEN.store(false, Ordering::SeqCst);
+    let level = if ok { Level::Info } else { Level::Error };
+    logging::log(level, format!("{msg}... {result}"));
+
+    if !ok && is_human() && !console(Level::Info) {
+        print!("{msg}... ");
+    }
 }
 
 pub fn process_msg_result(rslt: bool) {
+    let result = match rslt {
+        true => tr!("tui.ok"),
+        false => tr!("tui.error"),
+    };
+    process_finish(rslt, &result);
+
+    if !console(if rslt { Level::Info } else { Level::Error }) {
+        return;
+    }
     if rslt {
-        println!("{}", "OK".bold().green());
+        println!("{}", result.bold().green());
     } else {
-        println!("{}", "ERROR".bold().red());
+        println!("{}", result.bold().red());
     }
 }
 
+/// В режиме JSON ошибка выводится событием `warning`
 pub fn process_msg_result_err<E: Display>(rslt: bool, err: Option<E>) {
+    let err = err.map(|e| e.to_string());
+    let result = match (rslt, &err) {
+        (true, _) => tr!("tui.ok"),
+        (false, Some(err)) => format!("{}: {err}", tr!("tui.error")),
+        (false, None) => tr!("tui.error"),
+    };
+    process_finish(rslt, &result);
+
+    if !is_human() {
+        if let (false, Some(err)) = (rslt, err) {
+            emit(&Event::Warning { message: err });
+        }
+        return;
+    }
     if rslt {
-        println!("{}", "OK".bold().green());
+        if console(Level::Info) {
+            println!("{}", tr!("tui.ok").bold().green());
+        }
     } else {
         match err {
-            Some(err) => println!("{}:\t{}", "ERROR".bold().red(), format!("({err})").dimmed()),
-            None => println!("{}", "ERROR".bold().red()),
+            Some(err) => println!(
+                "{}:\t{}",
+                tr!("tui.error").bold().red(),
+                format!("({err})").dimmed()
+            ),
+            None => println!("{}", tr!("tui.error").bold().red()),
+        }
+    }
+}
+
+/// Результат операции, которую не потребовалось выполнять (e.g. `exists`)
+pub fn process_msg_result_skip<M: Display>(msg: M) {
+    process_finish(true, &msg.to_string());
+    if console(Level::Info) {
+        println!("{}", msg.to_string().bold().yellow());
+    }
+}
+
+/// Задаёт вопрос с ответом "да/нет". Если вопросы задавать нельзя, ответ
+/// определяется политикой ([`Policy`]): `--yes`, `--no` или ответ
+/// `default`.
+pub fn yesno<M: Display>(msg: M, default: bool) -> bool {
+    let rslt = match policy() {
+        Policy::Ask => None,
+        Policy::Yes => Some(true),
+        Policy::No => Some(false),
+        Policy::NonInteractive => Some(default),
+    };
+
+    if let Some(rslt) = rslt {
+        logging::info(format!("{msg} [y/n] {}", if rslt { "y" } else { "n" }));
+        if is_human() {
+            println!(
+                " :: {} {} {}",
+                msg.to_string().bold().magenta(),
+                "[y/n]".dimmed(),
+                tr!("tui.auto_answer", answer = if rslt { "y" } else { "n" }).dimmed()
+            );
         }
+        return rslt;
     }
+
+    print!(
+        " :: {} {} ",
+        msg.to_string().bold().magenta(),
+        "[y/n]".dimmed()
+    );
+    let _ = stdout().flush();
+
+    enable_echo_input();
+    let g = Getch::new();
+
+    let rslt = matches!(g.getch(), Ok(Key::Char('y')) | Ok(Key::Char('Y')));
+    logging::info(format!("{msg} [y/n] {}", if rslt { "y" } else { "n" }));
+
+    println!();
+
+    rslt
 }
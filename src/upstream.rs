@@ -0,0 +1,488 @@
+//! Upstream version checks (`alfa outdated`)
+//!
+//! Для пакета в `packages.toml` может быть указано правило проверки новых
+//! версий (поле `check`):
+//!
+//! - `listing` - листинг директории (e.g. `https://ftp.gnu.org/gnu/gmp/`);
+//!   версии извлекаются из страницы регулярным выражением `regex`;
+//! - `releases` - лента релизов в формате GitHub API (JSON-массив объектов
+//!   с полем `tag_name`);
+//! - `git` - теги git-репозитория, доступного по HTTP (`<url>/info/refs`).
+//!
+//! Версией считается первая группа регулярного выражения (или всё
+//! совпадение, если групп нет). Для `releases` и `git` выражение
+//! применяется к именам тегов и по умолчанию равно [`DEFAULT_TAG_REGEX`].
+//! Из найденных версий выбирается наибольшая (см. [`cmp_versions`]).
+
+use anyhow::{Error, Result};
+use colored::Colorize;
+use regex::Regex;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::{cmp::Ordering, time::Duration};
+
+use crate::build_meta::PackageList;
+use crate::events::{emit, is_human, Event};
+use crate::host::cmp_versions;
+use crate::logging;
+use crate::tr;
+
+/// Регулярное выражение для тегов по умолчанию (`v1.2.3`, `1.2.3`)
+pub const DEFAULT_TAG_REGEX: &str = r"^v?([0-9]+(?:\.[0-9]+)*)$";
+
+/// Время ожидания ответа сервера
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Правило проверки новых версий пакета
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum UpstreamCheck {
+    /// Листинг директории с архивами
+    Listing { url: String, regex: String },
+
+    /// Лента релизов в формате GitHub API
+    Releases {
+        url: String,
+
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        regex: Option<String>,
+
+        /// Учитывать предварительные релизы (`prerelease`)
+        #[serde(default)]
+        prerelease: bool,
+    },
+
+    /// Теги git-репозитория
+    Git {
+        url: String,
+
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        regex: Option<String>,
+    },
+}
+
+impl UpstreamCheck {
+    /// Адрес, по которому запрашиваются версии
+    pub fn fetch_url(&self) -> String {
+        match self {
+            Self::Listing { url, .. } | Self::Releases { url, .. } => url.clone(),
+            Self::Git { url, .. } => format!(
+                "{}/info/refs?service=git-upload-pack",
+                url.trim_end_matches('/')
+            ),
+        }
+    }
+
+    /// Все версии, найденные по правилу в ответе сервера `body`
+    pub fn parse(&self, body: &str) -> Result<Vec<String>> {
+        match self {
+            Self::Listing { regex, .. } => {
+                let re = compile(regex)?;
+                Ok(re.captures_iter(body).map(|c| capture(&c)).collect())
+            }
+            Self::Releases {
+                url,
+                regex,
+                prerelease,
+            } => {
+                let re = compile(regex.as_deref().unwrap_or(DEFAULT_TAG_REGEX))?;
+                let feed: serde_json::Value = serde_json::from_str(body)
+                    .map_err(|why| Error::msg(tr!("upstream.bad_feed", url = url, why = why)))?;
+                let releases = feed.as_array().ok_or(Error::msg(tr!(
+                    "upstream.bad_feed",
+                    url = url,
+                    why = "not an array"
+                )))?;
+
+                let is = |rel: &serde_json::Value, key| rel[key].as_bool().unwrap_or(false);
+                Ok(releases
+                    .iter()
+                    .filter(|rel| !is(rel, "draft") && (*prerelease || !is(rel, "prerelease")))
+                    .filter_map(|rel| rel["tag_name"].as_str())
+                    .filter_map(|tag| re.captures(tag).map(|c| capture(&c)))
+                    .collect())
+            }
+            Self::Git { regex, .. } => {
+                let re = compile(regex.as_deref().unwrap_or(DEFAULT_TAG_REGEX))?;
+                // ответ "умного" (pkt-line) и "простого" (файл `info/refs`)
+                // протоколов содержит строки вида `<sha> refs/tags/<tag>`
+                let tag_re = Regex::new(r"refs/tags/([^\s^{}\x00]+)")?;
+                Ok(tag_re
+                    .captures_iter(body)
+                    .filter_map(|c| re.captures(&c[1]).map(|c| capture(&c)))
+                    .collect())
+            }
+        }
+    }
+
+    /// Все версии, найденные по правилу
+    pub fn versions(&self, client: &Client) -> Result<Vec<String>> {
+        self.parse(&fetch(client, &self.fetch_url())?)
+    }
+
+    /// Наибольшая из найденных версий
+    pub fn latest(&self, client: &Client) -> Result<Option<String>> {
+        Ok(self
+            .versions(client)?
+            .into_iter()
+            .max_by(|a, b| cmp_versions(a, b)))
+    }
+}
+
+fn compile(regex: &str) -> Result<Regex> {
+    Regex::new(regex).map_err(|why| Error::msg(tr!("upstream.bad_regex", regex = regex, why = why)))
+}
+
+fn capture(c: &regex::Captures) -> String {
+    c.get(1).unwrap_or(c.get(0).unwrap()).as_str().to_string()
+}
+
+#[tokio::main]
+async fn fetch(client: &Client, url: &str) -> Result<String> {
+    logging::debug(tr!("download.get", url = url));
+    let res = client
+        .get(url)
+        .header("User-Agent", concat!("alfa/", env!("CARGO_PKG_VERSION")))
+        .timeout(TIMEOUT)
+        .send()
+        .await
+        .and_then(|res| res.error_for_status())?;
+
+    Ok(res.text().await?)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpstreamStatus {
+    UpToDate,
+    Outdated,
+
+    /// Пакет новее найденных версий (e.g. версия из ветки разработки)
+    Newer,
+
+    /// Версии не найдены или проверка завершилась ошибкой
+    Unknown,
+}
+
+/// Результат проверки пакета
+#[derive(Debug, Serialize)]
+pub struct Outdated {
+    pub package: String,
+    pub current: String,
+    pub latest: Option<String>,
+    pub status: UpstreamStatus,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Проверяет новые версии всех пакетов, для которых указано правило
+/// проверки. Возвращает результаты (в алфавитном порядке) и имена пакетов
+/// без правила.
+pub fn check_all(packages: &PackageList, client: &Client) -> (Vec<Outdated>, Vec<String>) {
+    let mut names = packages.package.keys().collect::<Vec<_>>();
+    names.sort_by_key(|name| name.to_lowercase());
+
+    let (mut results, mut unchecked) = (Vec::new(), Vec::new());
+    for name in names {
+        let pkg = &packages.package[name];
+        let Some(check) = &pkg.check else {
+            unchecked.push(name.clone());
+            continue;
+        };
+
+        let (latest, error) = match check.latest(client) {
+            Ok(latest) => (latest, None),
+            Err(why) => (None, Some(format!("{why:#}"))),
+        };
+        let status = match &latest {
+            None => UpstreamStatus::Unknown,
+            Some(latest) => match cmp_versions(&pkg.version, latest) {
+                Ordering::Less => UpstreamStatus::Outdated,
+                Ordering::Equal => UpstreamStatus::UpToDate,
+                Ordering::Greater => UpstreamStatus::Newer,
+            },
+        };
+        logging::info(tr!(
+            "upstream.checked",
+            name = name,
+            current = &pkg.version,
+            latest = latest.as_deref().unwrap_or("?"),
+            status = format!("{status:?}")
+        ));
+
+        results.push(Outdated {
+            package: name.clone(),
+            current: pkg.version.clone(),
+            latest,
+            status,
+            error,
+        });
+    }
+
+    (results, unchecked)
+}
+
+/// Выводит результаты проверки в виде таблицы (события `upstream` в режиме
+/// JSON)
+pub fn print_outdated(results: &[Outdated]) {
+    if !is_human() {
+        for rslt in results {
+            emit(&Event::Upstream(rslt));
+        }
+        return;
+    }
+
+    let headers = [
+        tr!("pkginfo.package"),
+        tr!("upstream.current"),
+        tr!("upstream.latest"),
+        tr!("upstream.status"),
+    ];
+    let width = |i: usize, col: &dyn Fn(&Outdated) -> usize| {
+        results
+            .iter()
+            .map(col)
+            .max()
+            .unwrap_or(0)
+            .max(headers[i].chars().count())
+    };
+    let w_name = width(0, &|r| r.package.chars().count());
+    let w_cur = width(1, &|r| r.current.chars().count());
+    let w_latest = width(2, &|r| r.latest.as_deref().unwrap_or("?").chars().count());
+
+    println!(
+        "{}",
+        format!(
+            "{:<w_name$}  {:<w_cur$}  {:<w_latest$}  {}",
+            &headers[0], &headers[1], &headers[2], &headers[3]
+        )
+        .bold()
+    );
+    for rslt in results {
+        let status = match rslt.status {
+            UpstreamStatus::UpToDate => tr!("upstream.up_to_date").green(),
+            UpstreamStatus::Outdated => tr!("upstream.outdated").bold().yellow(),
+            UpstreamStatus::Newer => tr!("upstream.newer").cyan(),
+            UpstreamStatus::Unknown => tr!("upstream.unknown").red(),
+        };
+        let error = match &rslt.error {
+            Some(why) => format!(" ({why})").dimmed().to_string(),
+            None => String::new(),
+        };
+        println!(
+            "{:<w_name$}  {:<w_cur$}  {:<w_latest$}  {status}{error}",
+            &rslt.package,
+            &rslt.current,
+            rslt.latest.as_deref().unwrap_or("?"),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build_meta::Package;
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    const LISTING: &str = r#"<html><body><pre>
+<a href="gmp-6.2.1.tar.xz">gmp-6.2.1.tar.xz</a>
+<a href="gmp-6.10.0.tar.xz">gmp-6.10.0.tar.xz</a>
+<a href="gmp-6.3.0.tar.xz">gmp-6.3.0.tar.xz</a>
+<a href="gmp-6.3.0.tar.xz.sig">gmp-6.3.0.tar.xz.sig</a>
+</pre></body></html>"#;
+
+    const RELEASES: &str = r#"[
+  {"tag_name": "v3.0.0", "draft": true, "prerelease": false},
+  {"tag_name": "v2.0.0", "draft": false, "prerelease": true},
+  {"tag_name": "v1.10.0", "draft": false, "prerelease": false},
+  {"tag_name": "v1.9.2", "draft": false, "prerelease": false},
+  {"tag_name": "nightly", "draft": false, "prerelease": false}
+]"#;
+
+    const INFO_REFS: &str = "001e# service=git-upload-pack\n\
+        0000\
+        003f1111111111111111111111111111111111111111 refs/heads/master\n\
+        003f2222222222222222222222222222222222222222 refs/tags/v2.44.0\n\
+        00423333333333333333333333333333333333333333 refs/tags/v2.44.0^{}\n\
+        003f4444444444444444444444444444444444444444 refs/tags/v2.45.1\n\
+        00435555555555555555555555555555555555555555 refs/tags/v2.46.0-rc0\n\
+        0000";
+
+    /// HTTP-сервер на 127.0.0.1, отдающий фикстуры по путям запросов.
+    /// Возвращает адрес вида `http://127.0.0.1:<порт>`.
+    fn serve() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut line = String::new();
+                let mut reader = BufReader::new(&stream);
+                reader.read_line(&mut line).unwrap();
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    if header.trim().is_empty() {
+                        break;
+                    }
+                }
+
+                let path = line.split_whitespace().nth(1).unwrap_or("/");
+                let (status, body) = match path {
+                    "/gnu/gmp/" => ("200 OK", LISTING),
+                    "/repos/foo/releases" => ("200 OK", RELEASES),
+                    "/git/bar.git/info/refs?service=git-upload-pack" => ("200 OK", INFO_REFS),
+                    _ => ("404 Not Found", "not found"),
+                };
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+            }
+        });
+
+        format!("http://{addr}")
+    }
+
+    fn client() -> Client {
+        Client::builder().no_proxy().build().unwrap()
+    }
+
+    fn listing(base: &str) -> UpstreamCheck {
+        UpstreamCheck::Listing {
+            url: format!("{base}/gnu/gmp/"),
+            regex: r"gmp-([0-9.]+)\.tar\.xz".to_string(),
+        }
+    }
+
+    fn releases(base: &str, prerelease: bool) -> UpstreamCheck {
+        UpstreamCheck::Releases {
+            url: format!("{base}/repos/foo/releases"),
+            regex: None,
+            prerelease,
+        }
+    }
+
+    fn git(base: &str) -> UpstreamCheck {
+        UpstreamCheck::Git {
+            url: format!("{base}/git/bar.git/"),
+            regex: None,
+        }
+    }
+
+    fn package(version: &str, check: Option<UpstreamCheck>) -> Package {
+        Package {
+            version: version.to_string(),
+            description: None,
+            home_page: None,
+            license: None,
+            download: String::new(),
+            md5: String::new(),
+            size_source: None,
+            size_build: None,
+            notes: None,
+            check,
+        }
+    }
+
+    #[test]
+    fn parse_listing() {
+        let mut versions = listing("http://localhost").parse(LISTING).unwrap();
+        versions.dedup();
+        assert_eq!(versions, ["6.2.1", "6.10.0", "6.3.0"]);
+    }
+
+    #[test]
+    fn parse_releases_skips_drafts_and_prereleases() {
+        let check = releases("http://localhost", false);
+        assert_eq!(check.parse(RELEASES).unwrap(), ["1.10.0", "1.9.2"]);
+
+        let check = releases("http://localhost", true);
+        assert_eq!(check.parse(RELEASES).unwrap(), ["2.0.0", "1.10.0", "1.9.2"]);
+
+        assert!(check.parse("{}").is_err());
+    }
+
+    #[test]
+    fn parse_git_refs() {
+        let versions = git("http://localhost").parse(INFO_REFS).unwrap();
+        assert_eq!(versions, ["2.44.0", "2.44.0", "2.45.1"]);
+    }
+
+    #[test]
+    fn latest_from_server() {
+        let base = serve();
+        let client = client();
+
+        let latest = |check: UpstreamCheck| check.latest(&client).unwrap();
+        assert_eq!(latest(listing(&base)).as_deref(), Some("6.10.0"));
+        assert_eq!(latest(releases(&base, false)).as_deref(), Some("1.10.0"));
+        assert_eq!(latest(releases(&base, true)).as_deref(), Some("2.0.0"));
+        assert_eq!(latest(git(&base)).as_deref(), Some("2.45.1"));
+
+        let missing = UpstreamCheck::Listing {
+            url: format!("{base}/missing/"),
+            regex: "x".to_string(),
+        };
+        assert!(missing.latest(&client).is_err());
+    }
+
+    #[test]
+    fn check_all_statuses() {
+        let base = serve();
+        let packages = PackageList {
+            package: [
+                ("gmp", package("6.3.0", Some(listing(&base)))),
+                ("foo", package("1.10.0", Some(releases(&base, false)))),
+                ("bar", package("2.50", Some(git(&base)))),
+                (
+                    "baz",
+                    package(
+                        "1.0",
+                        Some(UpstreamCheck::Git {
+                            url: format!("{base}/git/missing.git"),
+                            regex: None,
+                        }),
+                    ),
+                ),
+                ("qux", package("1.0", None)),
+            ]
+            .into_iter()
+            .map(|(name, pkg)| (name.to_string(), pkg))
+            .collect(),
+        };
+
+        let (results, unchecked) = check_all(&packages, &client());
+        assert_eq!(unchecked, ["qux"]);
+
+        let status = |name: &str| {
+            let rslt = results.iter().find(|r| r.package == name).unwrap();
+            (rslt.latest.as_deref(), rslt.status, rslt.error.is_some())
+        };
+        assert_eq!(
+            status("gmp"),
+            (Some("6.10.0"), UpstreamStatus::Outdated, false)
+        );
+        assert_eq!(
+            status("foo"),
+            (Some("1.10.0"), UpstreamStatus::UpToDate, false)
+        );
+        assert_eq!(
+            status("bar"),
+            (Some("2.45.1"), UpstreamStatus::Newer, false)
+        );
+        assert_eq!(status("baz"), (None, UpstreamStatus::Unknown, true));
+
+        let order = results
+            .iter()
+            .map(|r| r.package.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(order, ["bar", "baz", "foo", "gmp"]);
+    }
+}
@@ -0,0 +1,379 @@
+//! Semantic validation of the target settings (`EnvDefault`)
+
+use anyhow::Result;
+
+use crate::config::{Bits, EnvDefault};
+use crate::error::AlfaError;
+use crate::tr;
+
+/// Известные уровни архитектуры ARM для 32-битных целей
+pub const ARCH_32: &[&str] = &[
+    "armv5te", "armv6", "armv6k", "armv6kz", "armv6t2", "armv7-a", "armv7ve", "armv8-a",
+];
+
+/// Известные уровни архитектуры ARM для 64-битных целей
+pub const ARCH_64: &[&str] = &[
+    "armv8-a",
+    "armv8.1-a",
+    "armv8.2-a",
+    "armv8.3-a",
+    "armv8.4-a",
+    "armv8.5-a",
+    "armv8.6-a",
+    "armv8.7-a",
+    "armv8.8-a",
+    "armv9-a",
+    "armv9.1-a",
+    "armv9.2-a",
+    "armv9.3-a",
+    "armv9.4-a",
+];
+
+/// Допустимые значения `-mfloat-abi`
+pub const FLOAT_ABI: &[&str] = &["soft", "softfp", "hard"];
+
+/// Известные значения `-mfpu` и минимальный уровень архитектуры для них
+pub const FPU: &[(&str, &str)] = &[
+    ("vfpv2", "armv5te"),
+    ("vfpv3", "armv7-a"),
+    ("vfpv3-d16", "armv7-a"),
+    ("vfpv3xd", "armv7-a"),
+    ("vfpv4", "armv7-a"),
+    ("vfpv4-d16", "armv7-a"),
+    ("neon", "armv7-a"),
+    ("neon-vfpv4", "armv7-a"),
+    ("fp-armv8", "armv8-a"),
+    ("neon-fp-armv8", "armv8-a"),
+    ("crypto-neon-fp-armv8", "armv8-a"),
+];
+
+/// Проверяет согласованность `bits`, `lfa_tgt`, `lfa_arch`, `lfa_float` и
+/// `lfa_fpu`. Возвращает список всех найденных проблем.
+pub fn check_env_default(env: &EnvDefault) -> Vec<String> {
+    let mut issues = Vec::new();
+
+    check_triplet(env, &mut issues);
+    let arch = check_arch(env, &mut issues);
+    check_float(env, arch, &mut issues);
+
+    issues
+}
+
+fn check_triplet(env: &EnvDefault, issues: &mut Vec<String>) {
+    let parts = env.lfa_tgt.split('-').collect::<Vec<_>>();
+    if parts.len() < 3 || parts.iter().any(|p| p.is_empty()) {
+        issues.push(tr!("validate.malformed_triplet", target = env.lfa_tgt));
+        return;
+    }
+
+    let cpu = parts[0];
+    let abi = parts[parts.len() - 1];
+    let is_eabi = abi.contains("eabi");
+
+    match env.bits {
+        Bits::Arm64 => {
+            if cpu != "aarch64" && cpu != "aarch64_be" {
+                issues.push(tr!("validate.not_arm64_triplet", target = env.lfa_tgt));
+            }
+            if is_eabi {
+                issues.push(tr!("validate.arm64_eabi", target = env.lfa_tgt, abi = abi));
+            }
+        }
+        Bits::Arm32 => {
+            if !cpu.starts_with("arm") {
+                issues.push(tr!("validate.not_arm32_triplet", target = env.lfa_tgt));
+            }
+            if !is_eabi {
+                issues.push(tr!(
+                    "validate.arm32_no_eabi",
+                    target = env.lfa_tgt,
+                    abi = abi
+                ));
+            }
+        }
+    }
+}
+
+/// Возвращает базовый уровень архитектуры (без расширений `+crc`, `+crypto`
+/// и т.д.), если он известен
+fn check_arch<'a>(env: &'a EnvDefault, issues: &mut Vec<String>) -> Option<&'a str> {
+    let base = env.lfa_arch.split('+').next().unwrap_or_default();
+    let known = match env.bits {
+        Bits::Arm32 => ARCH_32,
+        Bits::Arm64 => ARCH_64,
+    };
+
+    if known.contains(&base) {
+        Some(base)
+    } else {
+        issues.push(tr!(
+            "validate.unknown_arch",
+            arch = env.lfa_arch,
+            bits = env.bits,
+            known = known.join(", ")
+        ));
+        None
+    }
+}
+
+fn check_float(env: &EnvDefault, arch: Option<&str>, issues: &mut Vec<String>) {
+    if let Bits::Arm64 = env.bits {
+        if let Some(float) = &env.lfa_float {
+            issues.push(tr!("validate.arm64_float", float = float));
+        }
+        if let Some(fpu) = &env.lfa_fpu {
+            issues.push(tr!("validate.arm64_fpu", fpu = fpu));
+        }
+        return;
+    }
+
+    let is_hf = env.lfa_tgt.ends_with("hf");
+    match env.lfa_float.as_deref() {
+        Some(float) if !FLOAT_ABI.contains(&float) => issues.push(tr!(
+            "validate.unknown_float",
+            float = float,
+            known = FLOAT_ABI.join(", ")
+        )),
+        Some("hard") if !is_hf => issues.push(tr!("validate.hard_not_hf", target = env.lfa_tgt)),
+        Some(float @ ("soft" | "softfp")) if is_hf => issues.push(tr!(
+            "validate.soft_on_hf",
+            float = float,
+            target = env.lfa_tgt
+        )),
+        _ => {}
+    }
+
+    let Some(fpu) = env.lfa_fpu.as_deref() else {
+        return;
+    };
+
+    if env.lfa_float.as_deref() == Some("soft") {
+        issues.push(tr!("validate.fpu_with_soft", fpu = fpu));
+    }
+
+    match FPU.iter().find(|(name, _)| *name == fpu) {
+        Some((_, min_arch)) => {
+            if let Some(arch) = arch {
+                if arch_level(arch) < arch_level(min_arch) {
+                    issues.push(tr!(
+                        "validate.fpu_arch",
+                        fpu = fpu,
+                        min_arch = min_arch,
+                        arch = arch
+                    ));
+                }
+            }
+        }
+        None => issues.push(tr!(
+            "validate.unknown_fpu",
+            fpu = fpu,
+            known = FPU
+                .iter()
+                .map(|(name, _)| *name)
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+    }
+}
+
+fn arch_level(arch: &str) -> usize {
+    ARCH_32.iter().position(|a| *a == arch).unwrap_or(0)
+}
+
+impl EnvDefault {
+    pub fn validate(&self) -> Result<()> {
+        let issues = check_env_default(self);
+        if issues.is_empty() {
+            return Ok(());
+        }
+
+        let mut msg = tr!("validate.invalid");
+        for issue in issues {
+            msg = format!("{msg}\n  - {issue}");
+        }
+
+        Err(AlfaError::Config(msg).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn env(
+        bits: Bits,
+        tgt: &str,
+        arch: &str,
+        float: Option<&str>,
+        fpu: Option<&str>,
+    ) -> EnvDefault {
+        EnvDefault {
+            bits,
+            lfa_host: "x86_64-cross-linux-gnu".to_string(),
+            lfa_tgt: tgt.to_string(),
+            lfa_arch: arch.to_string(),
+            lfa_float: float.map(String::from),
+            lfa_fpu: fpu.map(String::from),
+        }
+    }
+
+    fn arm32(tgt: &str, arch: &str, float: Option<&str>, fpu: Option<&str>) -> EnvDefault {
+        env(Bits::Arm32, tgt, arch, float, fpu)
+    }
+
+    #[test]
+    fn defaults_are_valid() {
+        assert!(check_env_default(&Config::defaults().env_default).is_empty());
+        assert!(check_env_default(&env(
+            Bits::Arm64,
+            "aarch64-linux-musl",
+            "armv8.1-a",
+            None,
+            None
+        ))
+        .is_empty());
+        assert!(check_env_default(&arm32(
+            "arm-linux-musleabihf",
+            "armv7-a",
+            Some("hard"),
+            Some("neon-vfpv4")
+        ))
+        .is_empty());
+    }
+
+    #[test]
+    fn triplet_abi() {
+        let e = env(
+            Bits::Arm64,
+            "aarch64-linux-gnueabihf",
+            "armv8-a",
+            None,
+            None,
+        );
+        assert_eq!(
+            check_env_default(&e),
+            [tr!(
+                "validate.arm64_eabi",
+                target = e.lfa_tgt,
+                abi = "gnueabihf"
+            )]
+        );
+
+        let e = arm32("arm-linux-musl", "armv7-a", None, None);
+        assert_eq!(
+            check_env_default(&e),
+            [tr!(
+                "validate.arm32_no_eabi",
+                target = e.lfa_tgt,
+                abi = "musl"
+            )]
+        );
+
+        let e = arm32("linux-musl", "armv7-a", None, None);
+        assert_eq!(
+            check_env_default(&e),
+            [tr!("validate.malformed_triplet", target = e.lfa_tgt)]
+        );
+    }
+
+    #[test]
+    fn float_and_fpu_on_arm64() {
+        let e = env(
+            Bits::Arm64,
+            "aarch64-linux-musl",
+            "armv8-a",
+            Some("hard"),
+            Some("neon"),
+        );
+        assert_eq!(
+            check_env_default(&e),
+            [
+                tr!("validate.arm64_float", float = "hard"),
+                tr!("validate.arm64_fpu", fpu = "neon"),
+            ]
+        );
+    }
+
+    #[test]
+    fn float_abi_and_triplet() {
+        let e = arm32("arm-linux-musleabi", "armv7-a", Some("hard"), None);
+        assert_eq!(
+            check_env_default(&e),
+            [tr!("validate.hard_not_hf", target = e.lfa_tgt)]
+        );
+
+        for float in ["soft", "softfp"] {
+            let e = arm32("arm-linux-musleabihf", "armv7-a", Some(float), None);
+            assert_eq!(
+                check_env_default(&e),
+                [tr!(
+                    "validate.soft_on_hf",
+                    float = float,
+                    target = e.lfa_tgt
+                )]
+            );
+        }
+
+        let e = arm32(
+            "arm-linux-musleabi",
+            "armv7-a",
+            Some("softfp"),
+            Some("vfpv3"),
+        );
+        assert!(check_env_default(&e).is_empty());
+    }
+
+    #[test]
+    fn fpu_with_soft_float() {
+        let e = arm32("arm-linux-musleabi", "armv7-a", Some("soft"), Some("neon"));
+        assert_eq!(
+            check_env_default(&e),
+            [tr!("validate.fpu_with_soft", fpu = "neon")]
+        );
+    }
+
+    #[test]
+    fn fpu_and_arch_level() {
+        let e = arm32("arm-linux-musleabihf", "armv6", Some("hard"), Some("neon"));
+        assert_eq!(
+            check_env_default(&e),
+            [tr!(
+                "validate.fpu_arch",
+                fpu = "neon",
+                min_arch = "armv7-a",
+                arch = "armv6"
+            )]
+        );
+
+        let e = arm32(
+            "arm-linux-musleabihf",
+            "armv7-a+mp+sec",
+            Some("hard"),
+            Some("neon"),
+        );
+        assert!(check_env_default(&e).is_empty());
+
+        let e = arm32(
+            "arm-linux-musleabihf",
+            "armv8-a+crc",
+            Some("hard"),
+            Some("neon-fp-armv8"),
+        );
+        assert!(check_env_default(&e).is_empty());
+    }
+
+    #[test]
+    fn unknown_values() {
+        let e = arm32(
+            "arm-linux-musleabihf",
+            "armv9-a",
+            Some("hard"),
+            Some("vfpv9"),
+        );
+        let issues = check_env_default(&e);
+        assert_eq!(issues.len(), 2);
+        assert!(issues[0].contains("armv9-a"));
+        assert!(issues[1].contains("vfpv9"));
+    }
+}